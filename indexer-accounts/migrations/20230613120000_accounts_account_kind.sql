@@ -0,0 +1,17 @@
+-- NAMED, NEAR_IMPLICIT (64 hex characters) or ETH_IMPLICIT (0x followed by 40 hex characters)
+ALTER TABLE accounts
+    ADD COLUMN account_kind text;
+
+-- Backfill of the already stored accounts, the same rules as in src/account_kind.rs
+UPDATE accounts
+SET account_kind = CASE
+                       WHEN account_id ~ '^[0-9a-f]{64}$' THEN 'NEAR_IMPLICIT'
+                       WHEN account_id ~ '^0x[0-9a-f]{40}$' THEN 'ETH_IMPLICIT'
+                       ELSE 'NAMED'
+    END
+WHERE account_kind IS NULL;
+
+ALTER TABLE accounts
+    ALTER COLUMN account_kind SET NOT NULL;
+
+CREATE INDEX CONCURRENTLY accounts_account_kind_idx ON accounts (account_kind);
@@ -0,0 +1,44 @@
+-- allowance is the initial allowance of the key, it is not updated while the key spends it
+-- allowance, receiver_id, method_names are NULL for FULL_ACCESS keys
+-- allowance is also NULL for FUNCTION_CALL keys with unlimited allowance
+-- method_names is empty if any method of receiver_id is allowed
+ALTER TABLE access_keys
+    ADD COLUMN nonce        numeric(20, 0),
+    ADD COLUMN allowance    numeric(45, 0),
+    ADD COLUMN receiver_id  text,
+    ADD COLUMN method_names text[];
+
+-- Backfill of the already stored keys from action_receipts__actions of indexer-base.
+-- The indexers could live in different DBs, so the backfill is skipped if indexer-base tables are not here
+DO
+$$
+    BEGIN
+        IF to_regclass('public.action_receipts__actions') IS NOT NULL THEN
+            UPDATE access_keys
+            SET nonce        = (actions.args -> 'access_key' ->> 'nonce')::numeric(20, 0),
+                allowance    = (actions.args -> 'access_key' -> 'permission' -> 'FunctionCall' ->> 'allowance')::numeric(45, 0),
+                receiver_id  = actions.args -> 'access_key' -> 'permission' -> 'FunctionCall' ->> 'receiver_id',
+                -- FULL_ACCESS keys get only the nonce here, their method_names stay NULL
+                method_names = CASE
+                                   WHEN access_keys.permission_kind = 'FUNCTION_CALL' THEN ARRAY(
+                                           SELECT jsonb_array_elements_text(
+                                                          actions.args -> 'access_key' -> 'permission' ->
+                                                          'FunctionCall' -> 'method_names')) END
+            FROM action_receipts__actions actions
+            WHERE actions.receipt_id = access_keys.created_by_receipt_id
+              AND actions.action_kind = 'ADD_KEY'
+              AND actions.args ->> 'public_key' = access_keys.public_key;
+        END IF;
+    END
+$$;
+
+-- The keys added by the Transfer to the implicit account and the genesis keys have no ADD_KEY action,
+-- they are FULL_ACCESS keys with nonce 0 (genesis keys could be imported again with `import-genesis`)
+UPDATE access_keys
+SET nonce = 0
+WHERE permission_kind = 'FULL_ACCESS'
+  AND nonce IS NULL;
+
+CREATE INDEX CONCURRENTLY access_keys_receiver_id_idx ON access_keys (receiver_id) WHERE receiver_id IS NOT NULL;
+CREATE INDEX CONCURRENTLY access_keys_unlimited_allowance_idx ON access_keys (account_id)
+    WHERE permission_kind = 'FUNCTION_CALL' AND allowance IS NULL;
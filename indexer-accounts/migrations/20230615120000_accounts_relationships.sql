@@ -0,0 +1,39 @@
+-- created_by_account_id is the predecessor of the receipt which created the account, NULL for genesis accounts
+-- parent_account_id is the parent of the named account (`near` for `alice.near`), NULL for top-level and implicit accounts
+-- deletion_beneficiary_id is the account which received the funds of the deleted account
+ALTER TABLE accounts
+    ADD COLUMN created_by_account_id   text,
+    ADD COLUMN parent_account_id       text,
+    ADD COLUMN deletion_beneficiary_id text;
+
+-- Backfill of the already stored accounts
+UPDATE accounts
+SET parent_account_id = substring(account_id FROM position('.' IN account_id) + 1)
+WHERE account_kind = 'NAMED'
+  AND position('.' IN account_id) > 0;
+
+-- The receipts are taken from indexer-base tables.
+-- The indexers could live in different DBs, so the backfill is skipped if indexer-base tables are not here
+DO
+$$
+    BEGIN
+        IF to_regclass('public.action_receipts') IS NOT NULL THEN
+            UPDATE accounts
+            SET created_by_account_id = action_receipts.predecessor_account_id
+            FROM action_receipts
+            WHERE action_receipts.receipt_id = accounts.created_by_receipt_id;
+        END IF;
+
+        IF to_regclass('public.action_receipts__actions') IS NOT NULL THEN
+            UPDATE accounts
+            SET deletion_beneficiary_id = actions.args ->> 'beneficiary_id'
+            FROM action_receipts__actions actions
+            WHERE actions.receipt_id = accounts.deleted_by_receipt_id
+              AND actions.action_kind = 'DELETE_ACCOUNT';
+        END IF;
+    END
+$$;
+
+CREATE INDEX CONCURRENTLY accounts_created_by_account_id_idx ON accounts (created_by_account_id);
+CREATE INDEX CONCURRENTLY accounts_parent_account_id_idx ON accounts (parent_account_id);
+CREATE INDEX CONCURRENTLY accounts_deletion_beneficiary_id_idx ON accounts (deletion_beneficiary_id);
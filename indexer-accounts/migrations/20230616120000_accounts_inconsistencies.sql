@@ -0,0 +1,14 @@
+-- The mismatches with JSON RPC found by `reconcile` subcommand
+-- public_key is empty for ACCOUNT_NOT_FOUND
+-- inconsistency_kind is ACCOUNT_NOT_FOUND, ACCESS_KEY_NOT_FOUND (alive in DB, absent in RPC)
+-- or ACCESS_KEY_MISSING (present in RPC, absent in DB)
+CREATE TABLE accounts_inconsistencies
+(
+    checked_at_block_height numeric(20, 0) NOT NULL,
+    account_id              text           NOT NULL,
+    public_key              text           NOT NULL,
+    inconsistency_kind      text           NOT NULL,
+    is_fixed                boolean        NOT NULL,
+    PRIMARY KEY (checked_at_block_height, account_id, public_key, inconsistency_kind)
+);
+CREATE INDEX CONCURRENTLY accounts_inconsistencies_account_id_idx ON accounts_inconsistencies (account_id);
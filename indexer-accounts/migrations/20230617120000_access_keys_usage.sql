@@ -0,0 +1,9 @@
+-- The last transaction signed by the key, NULL if the key has not been used since the indexer start
+ALTER TABLE access_keys
+    ADD COLUMN last_used_block_height     numeric(20, 0),
+    ADD COLUMN last_used_transaction_hash text,
+    ADD COLUMN last_nonce                 numeric(20, 0);
+
+-- Helps to find the keys unused for a long time
+CREATE INDEX CONCURRENTLY access_keys_last_used_block_height_idx ON access_keys (last_used_block_height)
+    WHERE deleted_by_block_height IS NULL;
@@ -0,0 +1,70 @@
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// The kind of the account by its id.
+/// Implicit accounts are created by the first `Transfer` to them, without `CreateAccount` action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKind {
+    Named,
+    NearImplicit,
+    EthImplicit,
+}
+
+/// The detectors of the implicit accounts, checked in order.
+/// To support a new kind of implicit accounts, add the variant above and its detector here
+const IMPLICIT_ACCOUNT_DETECTORS: &[(AccountKind, fn(&str) -> bool)] = &[
+    (AccountKind::NearImplicit, is_near_implicit),
+    (AccountKind::EthImplicit, is_eth_implicit),
+];
+
+impl AccountKind {
+    pub fn from_account_id(account_id: &AccountId) -> Self {
+        IMPLICIT_ACCOUNT_DETECTORS
+            .iter()
+            .find(|(_, detector)| detector(account_id.as_str()))
+            .map(|(kind, _)| *kind)
+            .unwrap_or(Self::Named)
+    }
+
+    pub fn is_implicit(&self) -> bool {
+        *self != Self::Named
+    }
+
+    /// The named account is the sub-account of its parent, e.g. `near` for `alice.near`.
+    /// Top-level and implicit accounts have no parent
+    pub fn parent_account_id(&self, account_id: &AccountId) -> Option<String> {
+        match self {
+            Self::Named => account_id
+                .as_str()
+                .split_once('.')
+                .map(|(_, parent)| parent.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Named => "NAMED",
+            Self::NearImplicit => "NEAR_IMPLICIT",
+            Self::EthImplicit => "ETH_IMPLICIT",
+        }
+    }
+}
+
+// 64 lowercase hex characters, the hex-encoded ED25519 public key
+fn is_near_implicit(account_id: &str) -> bool {
+    account_id.len() == 64 && is_lowercase_hex(account_id)
+}
+
+// `0x` followed by 40 lowercase hex characters, the Ethereum address
+fn is_eth_implicit(account_id: &str) -> bool {
+    account_id.len() == 42
+        && account_id
+            .strip_prefix("0x")
+            .map_or(false, is_lowercase_hex)
+}
+
+fn is_lowercase_hex(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
@@ -0,0 +1,132 @@
+use actix_web::{get, web, App, HttpResponse, HttpServer};
+
+use crate::LOGGING_PREFIX;
+
+#[derive(Debug, serde::Serialize)]
+struct AccountAtBlockResponse {
+    account_id: String,
+    block_height: u64,
+    exists: bool,
+    account: Option<crate::queries::AccountAtBlock>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct KeysQuery {
+    block_height: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct KeysResponse {
+    account_id: String,
+    block_height: Option<u64>,
+    keys: Vec<crate::queries::AccessKeyAtBlock>,
+}
+
+/// Did the account exist after the block
+#[get("/accounts/{account_id}/at/{block_height}")]
+async fn get_account_at_block(
+    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
+    path: web::Path<(String, u64)>,
+) -> actix_web::Result<HttpResponse> {
+    let (account_id, block_height) = path.into_inner();
+    let account = crate::queries::account_at_block(&pool, &account_id, block_height)
+        .await
+        .map_err(internal_error)?;
+    Ok(HttpResponse::Ok().json(AccountAtBlockResponse {
+        account_id,
+        block_height,
+        exists: account.is_some(),
+        account,
+    }))
+}
+
+/// Which keys could sign for the account after the block (or now, if `block_height` is not given)
+#[get("/accounts/{account_id}/keys")]
+async fn get_account_keys(
+    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
+    path: web::Path<String>,
+    query: web::Query<KeysQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let account_id = path.into_inner();
+    let keys = crate::queries::access_keys_at_block(&pool, &account_id, query.block_height)
+        .await
+        .map_err(internal_error)?;
+    Ok(HttpResponse::Ok().json(KeysResponse {
+        account_id,
+        block_height: query.block_height,
+        keys,
+    }))
+}
+
+fn internal_error(err: anyhow::Error) -> actix_web::Error {
+    tracing::error!(target: LOGGING_PREFIX, "API query failed: {}", err);
+    actix_web::error::ErrorInternalServerError("query failed")
+}
+
+pub(crate) fn init_server(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    port: u16,
+) -> anyhow::Result<actix_web::dev::Server> {
+    tracing::info!(
+        target: LOGGING_PREFIX,
+        "Starting API server on http://0.0.0.0:{port}/accounts"
+    );
+
+    let pool = web::Data::new(pool);
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(pool.clone())
+            .service(get_account_at_block)
+            .service(get_account_keys)
+    })
+    .bind(("0.0.0.0", port))?
+    .disable_signals()
+    .run())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use super::*;
+    use crate::queries::tests::{remove_history, store_history, test_pool};
+
+    #[actix_web::test]
+    async fn serves_the_account_and_keys_history() -> anyhow::Result<()> {
+        let pool = match test_pool().await? {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        let account_id = store_history(&pool).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(get_account_at_block)
+                .service(get_account_keys),
+        )
+        .await;
+
+        let mut responses: Vec<serde_json::Value> = vec![];
+        for uri in [
+            format!("/accounts/{}/at/25", account_id),
+            format!("/accounts/{}/at/30", account_id),
+            format!("/accounts/{}/keys", account_id),
+            format!("/accounts/{}/keys?block_height=17", account_id),
+        ] {
+            let request = test::TestRequest::get().uri(&uri).to_request();
+            responses.push(test::call_and_read_body_json(&app, request).await);
+        }
+        remove_history(&pool, &account_id).await?;
+
+        assert_eq!(responses[0]["exists"], true);
+        assert_eq!(responses[0]["account"]["created_by_block_height"], "20");
+        assert_eq!(responses[1]["exists"], false);
+        assert_eq!(responses[1]["account"], serde_json::Value::Null);
+        assert_eq!(responses[2]["block_height"], serde_json::Value::Null);
+        assert_eq!(responses[2]["keys"][0]["public_key"], "b");
+        assert_eq!(responses[2]["keys"].as_array().map(Vec::len), Some(2));
+        assert_eq!(responses[3]["block_height"], 17);
+        assert_eq!(responses[3]["keys"].as_array().map(Vec::len), Some(2));
+        Ok(())
+    }
+}
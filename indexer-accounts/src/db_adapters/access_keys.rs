@@ -1,141 +1,111 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
-use futures::future::try_join_all;
-use futures::try_join;
-
 use near_lake_framework::near_indexer_primitives;
 
+use crate::account_kind::AccountKind;
+use crate::db_adapters::mutations::Mutation;
 use crate::models;
 
-pub(crate) async fn store_access_keys(
-    pool: &sqlx::Pool<sqlx::Postgres>,
-    shards: &[near_indexer_primitives::IndexerShard],
-    block_height: near_indexer_primitives::types::BlockHeight,
-) -> anyhow::Result<()> {
-    let futures = shards.iter().map(|shard| {
-        store_access_keys_for_chunk(pool, &shard.receipt_execution_outcomes, block_height)
-    });
-
-    try_join_all(futures).await.map(|_| ())
-}
-
-async fn store_access_keys_for_chunk(
-    pool: &sqlx::Pool<sqlx::Postgres>,
-    outcomes: &[near_indexer_primitives::IndexerExecutionOutcomeWithReceipt],
+/// Collects the changes of `access_keys` made by the action of the successful receipt
+pub(crate) fn collect_access_key_mutations(
+    receipt: &near_indexer_primitives::views::ReceiptView,
+    action: &near_indexer_primitives::views::ActionView,
     block_height: near_indexer_primitives::types::BlockHeight,
-) -> anyhow::Result<()> {
-    if outcomes.is_empty() {
-        return Ok(());
-    }
-    let successful_receipts = outcomes
-        .iter()
-        .filter(|outcome_with_receipt| {
-            matches!(
-                outcome_with_receipt.execution_outcome.outcome.status,
-                near_indexer_primitives::views::ExecutionStatusView::SuccessValue(_)
-                    | near_indexer_primitives::views::ExecutionStatusView::SuccessReceiptId(_)
-            )
-        })
-        .map(|outcome_with_receipt| &outcome_with_receipt.receipt);
-
-    let mut created_access_keys: Vec<models::access_keys::AccessKey> = vec![];
-    let mut deleted_access_keys: Vec<models::access_keys::AccessKey> = vec![];
-    let mut access_keys_from_deleted_accounts: Vec<models::access_keys::AccessKey> = vec![];
-
-    for receipt in successful_receipts {
-        if let near_indexer_primitives::views::ReceiptEnumView::Action { actions, .. } =
-            &receipt.receipt
-        {
-            for action in actions {
-                match action {
-                    near_indexer_primitives::views::ActionView::DeleteAccount { .. } => {
-                        access_keys_from_deleted_accounts.push(
-                            models::access_keys::AccessKey::access_key_to_delete(
-                                "".to_string(),
+    mutations: &mut Vec<Mutation>,
+) {
+    match action {
+        near_indexer_primitives::views::ActionView::DeleteAccount { .. } => {
+            mutations.push(Mutation::DeleteAccountAccessKeys(
+                models::access_keys::AccessKey::access_key_to_delete(
+                    "".to_string(),
+                    &receipt.receiver_id,
+                    &receipt.receipt_id,
+                    block_height,
+                ),
+            ));
+        }
+        near_indexer_primitives::views::ActionView::AddKey {
+            public_key,
+            access_key,
+        } => {
+            mutations.push(Mutation::CreateAccessKey(
+                models::access_keys::AccessKey::from_action_view(
+                    public_key,
+                    &receipt.receiver_id,
+                    access_key,
+                    &receipt.receipt_id,
+                    block_height,
+                ),
+            ));
+        }
+        near_indexer_primitives::views::ActionView::DeleteKey { public_key } => {
+            mutations.push(Mutation::DeleteAccessKey(
+                models::access_keys::AccessKey::access_key_to_delete(
+                    public_key.to_string(),
+                    &receipt.receiver_id,
+                    &receipt.receipt_id,
+                    block_height,
+                ),
+            ));
+        }
+        near_indexer_primitives::views::ActionView::Transfer { .. } => {
+            // Only NEAR-implicit accounts get the access key derived from the account id,
+            // ETH-implicit ones are created without keys
+            if AccountKind::from_account_id(&receipt.receiver_id) == AccountKind::NearImplicit {
+                // we can just insert it, the duplicates will be ignored by the db
+                if let Ok(public_key_bytes) = hex::decode(receipt.receiver_id.as_ref()) {
+                    if let Ok(public_key) =
+                        near_crypto::ED25519PublicKey::try_from(&public_key_bytes[..])
+                    {
+                        mutations.push(Mutation::CreateAccessKey(
+                            models::access_keys::AccessKey::from_action_view(
+                                &near_crypto::PublicKey::from(public_key.clone()),
                                 &receipt.receiver_id,
+                                &near_indexer_primitives::views::AccessKeyView {
+                                    nonce: 0,
+                                    permission: near_indexer_primitives::views::AccessKeyPermissionView::FullAccess,
+                                },
                                 &receipt.receipt_id,
                                 block_height,
                             ),
-                        );
-                    }
-                    near_indexer_primitives::views::ActionView::AddKey {
-                        public_key,
-                        access_key,
-                    } => {
-                        created_access_keys.push(models::access_keys::AccessKey::from_action_view(
-                            public_key,
-                            &receipt.receiver_id,
-                            access_key,
-                            &receipt.receipt_id,
-                            block_height,
                         ));
                     }
-                    near_indexer_primitives::views::ActionView::DeleteKey { public_key } => {
-                        deleted_access_keys.push(
-                            models::access_keys::AccessKey::access_key_to_delete(
-                                public_key.to_string(),
-                                &receipt.receiver_id,
-                                &receipt.receipt_id,
-                                block_height,
-                            ),
-                        );
-                    }
-                    near_indexer_primitives::views::ActionView::Transfer { .. } => {
-                        if receipt.receiver_id.len() == 64usize {
-                            // we can just insert it, the duplicates will be ignored by the db
-                            if let Ok(public_key_bytes) = hex::decode(receipt.receiver_id.as_ref())
-                            {
-                                if let Ok(public_key) =
-                                    near_crypto::ED25519PublicKey::try_from(&public_key_bytes[..])
-                                {
-                                    created_access_keys.push(
-                                        models::access_keys::AccessKey::from_action_view(
-                                            &near_crypto::PublicKey::from(public_key.clone()),
-                                            &receipt.receiver_id,
-                                            &near_indexer_primitives::views::AccessKeyView {
-                                                nonce: 0,
-                                                permission: near_indexer_primitives::views::AccessKeyPermissionView::FullAccess
-                                            },
-                                            &receipt.receipt_id,
-                                            block_height
-                                        ),
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    _ => continue,
                 }
             }
         }
+        _ => {}
     }
+}
 
-    let update_access_keys_for_deleted_accounts_future = async {
-        let query = r"UPDATE access_keys
-                            SET deleted_by_receipt_id = $4, deleted_by_block_height = $6
-                            WHERE account_id = $2
-                                AND created_by_block_height < $6
-                                AND deleted_by_block_height IS NULL";
-        models::update_retry_or_panic(pool, query, &access_keys_from_deleted_accounts, 10).await
-    };
-
-    let update_access_keys_future = async {
-        let query = r"UPDATE access_keys
-                            SET deleted_by_receipt_id = $4, deleted_by_block_height = $6
-                            WHERE account_id = $2 AND public_key = $1
-                                AND created_by_block_height < $6
-                                AND deleted_by_block_height IS NULL";
-        models::update_retry_or_panic(pool, query, &deleted_access_keys, 10).await
-    };
-
-    let add_access_keys_future =
-        async { models::chunked_insert(pool, &created_access_keys, 10).await };
-
-    try_join!(
-        update_access_keys_for_deleted_accounts_future,
-        update_access_keys_future,
-        add_access_keys_future
-    )?;
-
-    Ok(())
+/// Updates the last usage of the access keys which signed the transactions of the block.
+/// The keys are updated with one batched query, the latest transaction of the key wins
+pub(crate) async fn store_access_keys_usage(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    shards: &[near_indexer_primitives::IndexerShard],
+    block_height: near_indexer_primitives::types::BlockHeight,
+) -> anyhow::Result<()> {
+    let mut usages: HashMap<(String, String), models::access_keys::AccessKeyUsage> = HashMap::new();
+    for transaction in shards
+        .iter()
+        .filter_map(|shard| shard.chunk.as_ref())
+        .flat_map(|chunk| chunk.transactions.iter())
+    {
+        let public_key = transaction.transaction.public_key.to_string();
+        let account_id = transaction.transaction.signer_id.to_string();
+        let usage = models::access_keys::AccessKeyUsage {
+            public_key: public_key.clone(),
+            account_id: account_id.clone(),
+            last_used_block_height: block_height.into(),
+            last_used_transaction_hash: transaction.transaction.hash.to_string(),
+            last_nonce: transaction.transaction.nonce.into(),
+        };
+        match usages.get(&(public_key.clone(), account_id.clone())) {
+            Some(existing) if existing.last_nonce >= usage.last_nonce => {}
+            _ => {
+                usages.insert((public_key, account_id), usage);
+            }
+        }
+    }
+    models::chunked_insert(pool, &usages.into_values().collect::<Vec<_>>(), 10).await
 }
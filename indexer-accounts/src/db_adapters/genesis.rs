@@ -1,130 +1,211 @@
-// TODO how to store genesis? How it's stored in S3?
-use actix_diesel::Database;
-use diesel::PgConnection;
-
-use crate::db_adapters::access_keys::store_access_keys_from_genesis;
-use crate::db_adapters::accounts::store_accounts_from_genesis;
-
-/// This is an ugly hack that allows to execute an async body on a specified actix runtime.
-/// You should only call it from a separate thread!
-///
-/// ```ignore
-/// async fn some_async_function() {
-///     let current_actix_system = actix::System::current();
-///     tokio::tasks::spawn_blocking(move || {
-///         let x = vec![0, 1, 2];
-///         x.map(|i| {
-///             block_on(current_actix_system, async move {
-///                 reqwest::get(...).await.text().await
-///             })
-///         });
-///     }
-/// }
-fn block_on<Fut, T>(
-    actix_arbiter: &actix_rt::ArbiterHandle,
-    f: Fut,
-) -> Result<T, std::sync::mpsc::RecvError>
-where
-    T: Send + 'static,
-    Fut: std::future::Future<Output = T> + Send + 'static,
-{
-    let (tx, rx) = std::sync::mpsc::channel();
-    actix_arbiter.spawn(async move {
-        let result = f.await;
-        let _ = tx.send(result);
-    });
-    rx.recv()
+use std::fmt;
+
+use near_lake_framework::near_indexer_primitives;
+use near_primitives::state_record::StateRecord;
+use serde::de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::models;
+
+const GENESIS_BATCH_SIZE: usize = 5_000;
+
+enum GenesisBatch {
+    Accounts(Vec<models::accounts::Account>),
+    AccessKeys(Vec<models::access_keys::AccessKey>),
 }
 
-/// Iterates over GenesisRecords and stores selected ones (Accounts, AccessKeys)
-/// to database.
-/// Separately stores records divided in portions by 5000 to optimize
-/// memory usage and minimize database queries
+/// Streams `genesis.json` (or the records file) and stores the accounts and access keys to database.
+/// The file is parsed on a separate thread in a blocking way, the records are passed here
+/// in portions by 5000 to optimize memory usage and minimize database queries
 pub(crate) async fn store_genesis_records(
-    pool: Database<PgConnection>,
-    near_config: near_indexer::NearConfig,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    args: &indexer_opts::ImportGenesisArgs,
 ) -> anyhow::Result<()> {
     tracing::info!(
-        target: crate::INDEXER_FOR_EXPLORER,
-        "Storing genesis records to database...",
+        target: crate::LOGGING_PREFIX,
+        "Storing genesis records from {} to database...",
+        args.genesis_path.display()
     );
-    let genesis_height = near_config.genesis.config.genesis_height;
-
-    // Remember the current actix runtime thread in order to be able to
-    // schedule async function on it from the thread that processes genesis in
-    // a blocking way.
-    let actix_system = actix::System::current();
-    // Spawn the blocking genesis processing on a separate thread
-    tokio::task::spawn_blocking(move || {
-        let actix_arbiter = actix_system.arbiter();
-
-        let mut accounts_to_store: Vec<crate::models::accounts::Account> = vec![];
-        let mut access_keys_to_store: Vec<crate::models::access_keys::AccessKey> = vec![];
-
-        near_config.genesis.for_each_record(|record| {
-            if accounts_to_store.len() == 5_000 {
-                let mut accounts_to_store_chunk = vec![];
-                std::mem::swap(&mut accounts_to_store, &mut accounts_to_store_chunk);
-                let pool = pool.clone();
-                block_on(
-                    actix_arbiter,
-                    store_accounts_from_genesis(pool, accounts_to_store_chunk),
-                )
-                .expect("storing accounts from genesis failed")
-                .expect("storing accounts from genesis failed");
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+    let genesis_path = args.genesis_path.clone();
+    let genesis_height = args.genesis_height;
+    let reader = tokio::task::spawn_blocking(move || {
+        read_genesis_records(&genesis_path, genesis_height, sender)
+    });
+
+    let mut accounts_count = 0;
+    let mut access_keys_count = 0;
+    while let Some(batch) = receiver.recv().await {
+        match batch {
+            GenesisBatch::Accounts(accounts) => {
+                models::chunked_insert(pool, &accounts, 10).await?;
+                accounts_count += accounts.len();
             }
-            if access_keys_to_store.len() == 5_000 {
-                let mut access_keys_to_store_chunk = vec![];
-                std::mem::swap(&mut access_keys_to_store, &mut access_keys_to_store_chunk);
-                let pool = pool.clone();
-                block_on(
-                    actix_arbiter,
-                    store_access_keys_from_genesis(pool, access_keys_to_store_chunk),
-                )
-                .expect("storing access keys from genesis failed")
-                .expect("storing access keys from genesis failed");
+            GenesisBatch::AccessKeys(access_keys) => {
+                models::chunked_insert(pool, &access_keys, 10).await?;
+                access_keys_count += access_keys.len();
             }
+        }
+    }
+    reader.await??;
+
+    tracing::info!(
+        target: crate::LOGGING_PREFIX,
+        "Genesis records has been stored: {} accounts, {} access keys",
+        accounts_count,
+        access_keys_count
+    );
+    Ok(())
+}
+
+fn read_genesis_records(
+    genesis_path: &std::path::Path,
+    genesis_height: Option<u64>,
+    sender: tokio::sync::mpsc::Sender<GenesisBatch>,
+) -> anyhow::Result<()> {
+    let file = std::fs::File::open(genesis_path).map_err(|err| {
+        anyhow::anyhow!("Failed to open genesis {}: {}", genesis_path.display(), err)
+    })?;
+    let mut collector = GenesisCollector {
+        genesis_height,
+        accounts: vec![],
+        access_keys: vec![],
+        sender,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(std::io::BufReader::new(file));
+    GenesisSeed(&mut collector).deserialize(&mut deserializer)?;
+    collector.flush()
+}
+
+struct GenesisCollector {
+    genesis_height: Option<u64>,
+    accounts: Vec<models::accounts::Account>,
+    access_keys: Vec<models::access_keys::AccessKey>,
+    sender: tokio::sync::mpsc::Sender<GenesisBatch>,
+}
 
-            match record {
-                near_indexer_primitives::state_record::StateRecord::Account {
-                    account_id,
-                    ..
-                } => {
-                    accounts_to_store.push(crate::models::accounts::Account::new_from_genesis(
-                        account_id,
+impl GenesisCollector {
+    fn add(&mut self, record: StateRecord) -> anyhow::Result<()> {
+        let genesis_height = self.genesis_height.ok_or_else(|| {
+            anyhow::anyhow!(
+                "`genesis_height` is not found before the records, use `--genesis-height`"
+            )
+        })?;
+        match record {
+            StateRecord::Account { account_id, .. } => {
+                self.accounts
+                    .push(models::accounts::Account::new_from_genesis(
+                        &account_id,
                         genesis_height,
                     ));
-                }
-                near_indexer_primitives::state_record::StateRecord::AccessKey {
-                    account_id,
-                    public_key,
-                    access_key,
-                } => {
-                    access_keys_to_store.push(crate::models::access_keys::AccessKey::from_genesis(
-                        public_key,
-                        account_id,
-                        access_key,
+            }
+            StateRecord::AccessKey {
+                account_id,
+                public_key,
+                access_key,
+            } => {
+                self.access_keys
+                    .push(models::access_keys::AccessKey::without_receipt(
+                        &public_key,
+                        &account_id,
+                        &near_indexer_primitives::views::AccessKeyView::from(access_key),
                         genesis_height,
                     ));
+            }
+            _ => {}
+        }
+        if self.accounts.len() == GENESIS_BATCH_SIZE {
+            self.send(GenesisBatch::Accounts(std::mem::take(&mut self.accounts)))?;
+        }
+        if self.access_keys.len() == GENESIS_BATCH_SIZE {
+            self.send(GenesisBatch::AccessKeys(std::mem::take(
+                &mut self.access_keys,
+            )))?;
+        }
+        Ok(())
+    }
+
+    fn flush(mut self) -> anyhow::Result<()> {
+        if !self.accounts.is_empty() {
+            self.send(GenesisBatch::Accounts(std::mem::take(&mut self.accounts)))?;
+        }
+        if !self.access_keys.is_empty() {
+            self.send(GenesisBatch::AccessKeys(std::mem::take(
+                &mut self.access_keys,
+            )))?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, batch: GenesisBatch) -> anyhow::Result<()> {
+        // The receiver is dropped only if the storing failed, the error is reported there
+        self.sender
+            .blocking_send(batch)
+            .map_err(|_| anyhow::anyhow!("Genesis records storing is interrupted"))
+    }
+}
+
+/// `genesis.json` is the object with `genesis_height` and `records` among other fields,
+/// the records file is the array of the records
+struct GenesisSeed<'a>(&'a mut GenesisCollector);
+
+impl<'de, 'a> DeserializeSeed<'de> for GenesisSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for GenesisSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("genesis config object or array of state records")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "genesis_height" => {
+                    let genesis_height = map.next_value::<u64>()?;
+                    // `--genesis-height` takes precedence
+                    self.0.genesis_height.get_or_insert(genesis_height);
+                }
+                "records" => map.next_value_seed(RecordsSeed(&mut *self.0))?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
                 }
-                _ => {}
-            };
-        });
-
-        let fut = || async move {
-            store_accounts_from_genesis(pool.clone(), accounts_to_store).await?;
-            store_access_keys_from_genesis(pool, access_keys_to_store).await?;
-            anyhow::Result::<()>::Ok(())
-        };
-        block_on(actix_arbiter, fut())
-            .expect("storing leftover accounts and access keys from genesis failed")
-            .expect("storing leftover accounts and access keys from genesis failed");
-    })
-    .await?;
+            }
+        }
+        Ok(())
+    }
 
-    tracing::info!(
-        target: crate::INDEXER_FOR_EXPLORER,
-        "Genesis records has been stored.",
-    );
-    Ok(())
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<(), A::Error> {
+        RecordsSeed(self.0).visit_seq(seq)
+    }
+}
+
+struct RecordsSeed<'a>(&'a mut GenesisCollector);
+
+impl<'de, 'a> DeserializeSeed<'de> for RecordsSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for RecordsSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("array of state records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(record) = seq.next_element::<StateRecord>()? {
+            self.0.add(record).map_err(A::Error::custom)?;
+        }
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 pub(crate) mod access_keys;
 pub(crate) mod accounts;
-// pub(crate) mod genesis;
+pub(crate) mod genesis;
+pub(crate) mod mutations;
 
 pub(crate) const CHUNK_SIZE_FOR_BATCH_INSERT: usize = 100;
@@ -0,0 +1,403 @@
+use near_lake_framework::near_indexer_primitives;
+
+use crate::models::{self, FieldCount, MySqlMethods};
+
+/// The change of `accounts` or `access_keys` made by the action.
+/// The changes of the block are applied one by one in the order of the receipts execution
+/// (shards in order, receipts in the order of their outcomes), inside one DB transaction
+#[derive(Debug)]
+pub(crate) enum Mutation {
+    CreateAccount(models::accounts::Account),
+    CreateImplicitAccount(models::accounts::Account),
+    DeleteAccount(models::accounts::Account),
+    CreateAccessKey(models::access_keys::AccessKey),
+    // The key found by `reconcile`, it fixes the row of the key not alive at the checked block
+    RestoreAccessKey(models::access_keys::AccessKey),
+    DeleteAccessKey(models::access_keys::AccessKey),
+    DeleteAccountAccessKeys(models::access_keys::AccessKey),
+}
+
+// The account created and deleted in the same block could be created again in this block,
+// the primary key does not allow to store both, so we keep the last one
+const RECREATE_ACCOUNT_ON_CONFLICT: &str = r"
+    ON CONFLICT (account_id, created_by_block_height) DO UPDATE
+        SET created_by_receipt_id = EXCLUDED.created_by_receipt_id,
+            created_by_account_id = EXCLUDED.created_by_account_id,
+            deleted_by_receipt_id = NULL,
+            deleted_by_block_height = NULL,
+            deletion_beneficiary_id = NULL
+        WHERE accounts.deleted_by_block_height = accounts.created_by_block_height";
+
+// The key exists at the checked block, but the row is deleted at or before it (the key was added again)
+// or created after it. The deletion at or before the block is cleared, and the exact block of the creation
+// is unknown, so the checked block is used as the upper bound
+const RESTORE_ACCESS_KEY_ON_CONFLICT: &str = r"
+    ON CONFLICT (public_key, account_id) DO UPDATE
+        SET created_by_receipt_id = NULL,
+            created_by_block_height = EXCLUDED.created_by_block_height,
+            deleted_by_receipt_id = CASE WHEN access_keys.deleted_by_block_height <= EXCLUDED.created_by_block_height
+                THEN NULL ELSE access_keys.deleted_by_receipt_id END,
+            deleted_by_block_height = CASE WHEN access_keys.deleted_by_block_height <= EXCLUDED.created_by_block_height
+                THEN NULL ELSE access_keys.deleted_by_block_height END,
+            permission_kind = EXCLUDED.permission_kind,
+            nonce = EXCLUDED.nonce,
+            allowance = EXCLUDED.allowance,
+            receiver_id = EXCLUDED.receiver_id,
+            method_names = EXCLUDED.method_names";
+
+impl Mutation {
+    fn query(&self) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::CreateAccount(_) => {
+                "INSERT INTO accounts VALUES ".to_owned()
+                    + &models::create_placeholders(1, models::accounts::Account::field_count())?
+                    + RECREATE_ACCOUNT_ON_CONFLICT
+            }
+            Self::CreateImplicitAccount(_) => {
+                r"INSERT INTO accounts
+                    SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM accounts
+                        WHERE account_id = $1
+                            AND created_by_block_height <= $4
+                            AND (deleted_by_block_height IS NULL OR deleted_by_block_height > $4)
+                    )"
+                .to_owned()
+                    + RECREATE_ACCOUNT_ON_CONFLICT
+            }
+            Self::DeleteAccount(_) => r"UPDATE accounts
+                    SET deleted_by_receipt_id = $3, deleted_by_block_height = $5, deletion_beneficiary_id = $9
+                    WHERE account_id = $1
+                        AND created_by_block_height <= $5
+                        AND deleted_by_block_height IS NULL"
+                .to_string(),
+            Self::CreateAccessKey(_) => models::access_keys::AccessKey::insert_query(1)?,
+            Self::RestoreAccessKey(_) => {
+                "INSERT INTO access_keys VALUES ".to_owned()
+                    + &models::create_placeholders(
+                        1,
+                        models::access_keys::AccessKey::field_count(),
+                    )?
+                    + RESTORE_ACCESS_KEY_ON_CONFLICT
+            }
+            Self::DeleteAccessKey(_) => r"UPDATE access_keys
+                    SET deleted_by_receipt_id = $4, deleted_by_block_height = $6
+                    WHERE account_id = $2 AND public_key = $1
+                        AND created_by_block_height <= $6
+                        AND deleted_by_block_height IS NULL"
+                .to_string(),
+            Self::DeleteAccountAccessKeys(_) => r"UPDATE access_keys
+                    SET deleted_by_receipt_id = $4, deleted_by_block_height = $6
+                    WHERE account_id = $2
+                        AND created_by_block_height <= $6
+                        AND deleted_by_block_height IS NULL"
+                .to_string(),
+        })
+    }
+
+    fn args(&self) -> sqlx::postgres::PgArguments {
+        let mut args = sqlx::postgres::PgArguments::default();
+        match self {
+            Self::CreateAccount(account)
+            | Self::CreateImplicitAccount(account)
+            | Self::DeleteAccount(account) => account.add_to_args(&mut args),
+            Self::CreateAccessKey(access_key)
+            | Self::RestoreAccessKey(access_key)
+            | Self::DeleteAccessKey(access_key)
+            | Self::DeleteAccountAccessKeys(access_key) => access_key.add_to_args(&mut args),
+        }
+        args
+    }
+}
+
+/// Collects the changes of `accounts` and `access_keys` from all the shards of the block
+/// and applies them in the order of execution
+pub(crate) async fn store_mutations(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    shards: &[near_indexer_primitives::IndexerShard],
+    block_height: near_indexer_primitives::types::BlockHeight,
+) -> anyhow::Result<()> {
+    let mutations = collect_mutations(shards, block_height);
+    if mutations.is_empty() {
+        return Ok(());
+    }
+    apply_retry_or_panic(pool, &mutations, 10).await.map(|_| ())
+}
+
+fn collect_mutations(
+    shards: &[near_indexer_primitives::IndexerShard],
+    block_height: near_indexer_primitives::types::BlockHeight,
+) -> Vec<Mutation> {
+    let successful_receipts = shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+        .filter(|outcome_with_receipt| {
+            matches!(
+                outcome_with_receipt.execution_outcome.outcome.status,
+                near_indexer_primitives::views::ExecutionStatusView::SuccessValue(_)
+                    | near_indexer_primitives::views::ExecutionStatusView::SuccessReceiptId(_)
+            )
+        })
+        .map(|outcome_with_receipt| &outcome_with_receipt.receipt);
+
+    let mut mutations = vec![];
+    for receipt in successful_receipts {
+        if let near_indexer_primitives::views::ReceiptEnumView::Action { actions, .. } =
+            &receipt.receipt
+        {
+            for action in actions {
+                crate::db_adapters::accounts::collect_account_mutations(
+                    receipt,
+                    action,
+                    block_height,
+                    &mut mutations,
+                );
+                crate::db_adapters::access_keys::collect_access_key_mutations(
+                    receipt,
+                    action,
+                    block_height,
+                    &mut mutations,
+                );
+            }
+        }
+    }
+    mutations
+}
+
+/// Returns the number of the rows changed by each mutation
+pub(crate) async fn apply_retry_or_panic(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    mutations: &[Mutation],
+    retry_count: usize,
+) -> anyhow::Result<Vec<u64>> {
+    let mut interval = crate::INTERVAL;
+    let mut retry_attempt = 0usize;
+
+    loop {
+        if retry_attempt == retry_count {
+            return Err(anyhow::anyhow!(
+                "Failed to perform query to database after {} attempts. Stop trying.",
+                retry_count
+            ));
+        }
+        retry_attempt += 1;
+
+        match apply(pool, mutations).await {
+            Ok(rows_affected) => return Ok(rows_affected),
+            Err(async_error) => {
+                tracing::error!(
+                    target: crate::LOGGING_PREFIX,
+                    "Error occurred during {}:\n{} account mutations were not applied. \n{:#?} \n Retrying in {} milliseconds...",
+                    async_error,
+                    mutations.len(),
+                    mutations,
+                    interval.as_millis(),
+                );
+                tokio::time::sleep(interval).await;
+                if interval < crate::MAX_DELAY_TIME {
+                    interval *= 2;
+                }
+            }
+        }
+    }
+}
+
+async fn apply(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    mutations: &[Mutation],
+) -> anyhow::Result<Vec<u64>> {
+    let mut transaction = pool.begin().await?;
+    let mut rows_affected = Vec::with_capacity(mutations.len());
+    for mutation in mutations {
+        let result = sqlx::query_with(&mutation.query()?, mutation.args())
+            .execute(&mut transaction)
+            .await?;
+        rows_affected.push(result.rows_affected());
+    }
+    transaction.commit().await?;
+    Ok(rows_affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_HEIGHT: u64 = 100;
+
+    fn receipt_id(name: &str) -> String {
+        near_primitives::hash::hash(name.as_bytes()).to_string()
+    }
+
+    fn public_key() -> String {
+        near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, "test")
+            .public_key()
+            .to_string()
+    }
+
+    /// The successful receipt with the actions in the Lake JSON format
+    fn outcome_with_receipt(
+        name: &str,
+        receiver_id: &str,
+        actions: serde_json::Value,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "execution_outcome": {
+                "proof": [],
+                "block_hash": receipt_id("block"),
+                "id": receipt_id(name),
+                "outcome": {
+                    "logs": [],
+                    "receipt_ids": [],
+                    "gas_burnt": 0,
+                    "tokens_burnt": "0",
+                    "executor_id": receiver_id,
+                    "status": {"SuccessValue": ""},
+                    "metadata": {"version": 1, "gas_profile": null}
+                }
+            },
+            "receipt": {
+                "predecessor_id": "near",
+                "receiver_id": receiver_id,
+                "receipt_id": receipt_id(name),
+                "receipt": {
+                    "Action": {
+                        "signer_id": "near",
+                        "signer_public_key": public_key(),
+                        "gas_price": "0",
+                        "output_data_receivers": [],
+                        "input_data_ids": [],
+                        "actions": actions
+                    }
+                }
+            }
+        })
+    }
+
+    /// The account is created, deleted and created again in one block, then its new key is added and deleted
+    fn shards(account_id: &str) -> Vec<near_indexer_primitives::IndexerShard> {
+        let add_key = serde_json::json!({
+            "AddKey": {
+                "public_key": public_key(),
+                "access_key": {"nonce": 0, "permission": "FullAccess"}
+            }
+        });
+        let delete_key = serde_json::json!({"DeleteKey": {"public_key": public_key()}});
+        let delete_account = serde_json::json!({"DeleteAccount": {"beneficiary_id": "near"}});
+        serde_json::from_value(serde_json::json!([
+            {
+                "shard_id": 0,
+                "chunk": null,
+                "receipt_execution_outcomes": [
+                    outcome_with_receipt("create", account_id, serde_json::json!(["CreateAccount"])),
+                    outcome_with_receipt("delete", account_id, serde_json::json!([delete_account])),
+                ],
+                "state_changes": []
+            },
+            {
+                "shard_id": 1,
+                "chunk": null,
+                "receipt_execution_outcomes": [
+                    outcome_with_receipt("recreate", account_id, serde_json::json!(["CreateAccount", add_key])),
+                    outcome_with_receipt("delete_key", account_id, serde_json::json!([delete_key])),
+                ],
+                "state_changes": []
+            }
+        ]))
+        .expect("Test shards expected to be valid")
+    }
+
+    fn describe(mutation: &Mutation) -> (&'static str, Option<String>) {
+        match mutation {
+            Mutation::CreateAccount(account) => {
+                ("CreateAccount", account.created_by_receipt_id.clone())
+            }
+            Mutation::CreateImplicitAccount(account) => (
+                "CreateImplicitAccount",
+                account.created_by_receipt_id.clone(),
+            ),
+            Mutation::DeleteAccount(account) => {
+                ("DeleteAccount", account.deleted_by_receipt_id.clone())
+            }
+            Mutation::CreateAccessKey(access_key) => {
+                ("CreateAccessKey", access_key.created_by_receipt_id.clone())
+            }
+            Mutation::RestoreAccessKey(access_key) => {
+                ("RestoreAccessKey", access_key.created_by_receipt_id.clone())
+            }
+            Mutation::DeleteAccessKey(access_key) => {
+                ("DeleteAccessKey", access_key.deleted_by_receipt_id.clone())
+            }
+            Mutation::DeleteAccountAccessKeys(access_key) => (
+                "DeleteAccountAccessKeys",
+                access_key.deleted_by_receipt_id.clone(),
+            ),
+        }
+    }
+
+    #[test]
+    fn collects_mutations_in_receipts_order() {
+        let mutations = collect_mutations(&shards("alice.near"), BLOCK_HEIGHT);
+        assert_eq!(
+            mutations.iter().map(describe).collect::<Vec<_>>(),
+            vec![
+                ("CreateAccount", Some(receipt_id("create"))),
+                ("DeleteAccount", Some(receipt_id("delete"))),
+                ("DeleteAccountAccessKeys", Some(receipt_id("delete"))),
+                ("CreateAccount", Some(receipt_id("recreate"))),
+                ("CreateAccessKey", Some(receipt_id("recreate"))),
+                ("DeleteAccessKey", Some(receipt_id("delete_key"))),
+            ]
+        );
+    }
+
+    /// Needs the migrated database in `TEST_DATABASE_URL`, skipped without it
+    #[tokio::test]
+    async fn applies_recreated_account() -> anyhow::Result<()> {
+        let database_url = match std::env::var("TEST_DATABASE_URL") {
+            Ok(database_url) => database_url,
+            Err(_) => return Ok(()),
+        };
+        let pool = sqlx::PgPool::connect(&database_url).await?;
+        let account_id = format!(
+            "recreated-{}.test.near",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_nanos()
+        );
+
+        apply(
+            &pool,
+            &collect_mutations(&shards(&account_id), BLOCK_HEIGHT),
+        )
+        .await?;
+
+        let accounts: Vec<(Option<String>, Option<bigdecimal::BigDecimal>)> = sqlx::query_as(
+            "SELECT created_by_receipt_id, deleted_by_block_height FROM accounts WHERE account_id = $1",
+        )
+        .bind(&account_id)
+        .fetch_all(&pool)
+        .await?;
+        let access_keys: Vec<(Option<String>, Option<String>)> = sqlx::query_as(
+            "SELECT created_by_receipt_id, deleted_by_receipt_id FROM access_keys WHERE account_id = $1",
+        )
+        .bind(&account_id)
+        .fetch_all(&pool)
+        .await?;
+        sqlx::query("DELETE FROM access_keys WHERE account_id = $1")
+            .bind(&account_id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("DELETE FROM accounts WHERE account_id = $1")
+            .bind(&account_id)
+            .execute(&pool)
+            .await?;
+
+        // The deleted row is replaced by the recreated one, and the account is alive
+        assert_eq!(accounts, vec![(Some(receipt_id("recreate")), None)]);
+        assert_eq!(
+            access_keys,
+            vec![(Some(receipt_id("recreate")), Some(receipt_id("delete_key")))]
+        );
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 // TODO cleanup imports in all the files in the end
-use futures::{try_join, StreamExt};
-use indexer_opts::Parser;
+use futures::StreamExt;
 use near_lake_framework::near_indexer_primitives;
 
+mod account_kind;
+mod api;
 mod configs;
 mod db_adapters;
 mod models;
+mod queries;
+mod reconcile;
 
 pub(crate) const LOGGING_PREFIX: &str = "indexer_accounts";
 
@@ -15,12 +18,28 @@ const MAX_DELAY_TIME: std::time::Duration = std::time::Duration::from_secs(120);
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
-    let opts = indexer_opts::Opts::parse();
+    let opts = indexer_opts::Opts::parse_with_config(env!("CARGO_PKG_NAME"))?;
     let _worker_guard = configs::init_tracing(opts.debug)?;
 
     let pool = sqlx::PgPool::connect(&opts.database_url).await?;
-    let lake_config = opts.to_lake_config(&pool).await?;
+    match &opts.command {
+        Some(indexer_opts::Command::ImportGenesis(args)) => {
+            let _lock = opts.lock_indexer(&pool).await?;
+            return db_adapters::genesis::store_genesis_records(&pool, args).await;
+        }
+        Some(indexer_opts::Command::Reconcile(args)) => {
+            return reconcile::run(&opts, args, &pool).await;
+        }
+        Some(indexer_opts::Command::Audit(_)) => {
+            anyhow::bail!("`audit` is not supported by indexer-accounts")
+        }
+        None => {}
+    }
+    let lake_config = opts
+        .to_lake_config(&pool, env!("CARGO_PKG_VERSION"))
+        .await?;
     let (sender, stream) = near_lake_framework::streamer(lake_config);
+    tokio::spawn(api::init_server(pool.clone(), opts.port).expect("Failed to start API server"));
     let end_block_height = opts.end_block_height.unwrap_or(u64::MAX);
 
     let mut handlers = tokio_stream::wrappers::ReceiverStream::new(stream)
@@ -63,18 +82,19 @@ async fn handle_streamer_message(
     streamer_message: near_indexer_primitives::StreamerMessage,
     pool: &sqlx::Pool<sqlx::Postgres>,
 ) -> anyhow::Result<u64> {
-    let accounts_future = db_adapters::accounts::store_accounts(
+    // accounts and access keys are changed in the order of execution, the deletion could follow the creation
+    db_adapters::mutations::store_mutations(
         pool,
         &streamer_message.shards,
         streamer_message.block.header.height,
-    );
-
-    let access_keys_future = db_adapters::access_keys::store_access_keys(
+    )
+    .await?;
+    // updates the stored access keys, so it goes after the mutations
+    db_adapters::access_keys::store_access_keys_usage(
         pool,
         &streamer_message.shards,
         streamer_message.block.header.height,
-    );
-
-    try_join!(accounts_future, access_keys_future)?;
+    )
+    .await?;
     Ok(streamer_message.block.header.height)
 }
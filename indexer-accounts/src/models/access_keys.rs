@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use bigdecimal::BigDecimal;
 use sqlx::Arguments;
 
@@ -14,6 +16,12 @@ pub struct AccessKey {
     pub created_by_block_height: BigDecimal,
     pub deleted_by_block_height: Option<BigDecimal>,
     pub permission_kind: String,
+    pub nonce: Option<BigDecimal>,
+    // NULL for FULL_ACCESS keys and for FUNCTION_CALL keys with unlimited allowance
+    pub allowance: Option<BigDecimal>,
+    pub receiver_id: Option<String>,
+    // Empty array means any method of `receiver_id` is allowed
+    pub method_names: Option<Vec<String>>,
 }
 
 impl AccessKey {
@@ -31,6 +39,33 @@ impl AccessKey {
             created_by_block_height: Default::default(),
             deleted_by_block_height: Some(BigDecimal::from(deleted_by_block_height)),
             permission_kind: "".to_string(),
+            nonce: None,
+            allowance: None,
+            receiver_id: None,
+            method_names: None,
+        }
+    }
+
+    /// The key which is not added by the receipt: from genesis or found by `reconcile`
+    pub fn without_receipt(
+        public_key: &near_crypto::PublicKey,
+        account_id: &near_indexer_primitives::types::AccountId,
+        access_key: &near_indexer_primitives::views::AccessKeyView,
+        created_by_block_height: near_indexer_primitives::types::BlockHeight,
+    ) -> Self {
+        let (allowance, receiver_id, method_names) = permission_details(&access_key.permission);
+        Self {
+            public_key: public_key.to_string(),
+            account_id: account_id.to_string(),
+            created_by_receipt_id: None,
+            deleted_by_receipt_id: None,
+            created_by_block_height: BigDecimal::from(created_by_block_height),
+            deleted_by_block_height: None,
+            permission_kind: access_key.permission.print().to_string(),
+            nonce: Some(BigDecimal::from(access_key.nonce)),
+            allowance,
+            receiver_id,
+            method_names,
         }
     }
 
@@ -41,6 +76,7 @@ impl AccessKey {
         created_by_receipt_id: &near_indexer_primitives::CryptoHash,
         created_by_block_height: near_indexer_primitives::types::BlockHeight,
     ) -> Self {
+        let (allowance, receiver_id, method_names) = permission_details(&access_key.permission);
         Self {
             public_key: public_key.to_string(),
             account_id: account_id.to_string(),
@@ -49,10 +85,40 @@ impl AccessKey {
             created_by_block_height: BigDecimal::from(created_by_block_height),
             deleted_by_block_height: None,
             permission_kind: access_key.permission.print().to_string(),
+            nonce: Some(BigDecimal::from(access_key.nonce)),
+            allowance,
+            receiver_id,
+            method_names,
         }
     }
 }
 
+/// Returns allowance, receiver_id and method_names of FUNCTION_CALL permission, NULLs for FULL_ACCESS
+fn permission_details(
+    permission: &near_indexer_primitives::views::AccessKeyPermissionView,
+) -> (Option<BigDecimal>, Option<String>, Option<Vec<String>>) {
+    match permission {
+        near_indexer_primitives::views::AccessKeyPermissionView::FunctionCall {
+            allowance,
+            receiver_id,
+            method_names,
+        } => (
+            allowance.map(|allowance| {
+                BigDecimal::from_str(&allowance.to_string())
+                    .expect("`allowance` expected to be u128")
+            }),
+            Some(receiver_id.to_string()),
+            Some(
+                method_names
+                    .iter()
+                    .map(|method_name| method_name.escape_default().to_string())
+                    .collect(),
+            ),
+        ),
+        near_indexer_primitives::views::AccessKeyPermissionView::FullAccess => (None, None, None),
+    }
+}
+
 impl crate::models::MySqlMethods for AccessKey {
     fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
         args.add(&self.public_key);
@@ -62,6 +128,10 @@ impl crate::models::MySqlMethods for AccessKey {
         args.add(&self.created_by_block_height);
         args.add(&self.deleted_by_block_height);
         args.add(&self.permission_kind);
+        args.add(&self.nonce);
+        args.add(&self.allowance);
+        args.add(&self.receiver_id);
+        args.add(&self.method_names);
     }
 
     fn insert_query(items_count: usize) -> anyhow::Result<String> {
@@ -74,3 +144,43 @@ impl crate::models::MySqlMethods for AccessKey {
         "access_keys".to_string()
     }
 }
+
+/// The last transaction signed by the access key.
+/// Unlike the other models, it updates the existing `access_keys` rows
+#[derive(Debug, FieldCount)]
+pub struct AccessKeyUsage {
+    pub public_key: String,
+    pub account_id: String,
+    pub last_used_block_height: BigDecimal,
+    pub last_used_transaction_hash: String,
+    pub last_nonce: BigDecimal,
+}
+
+impl crate::models::MySqlMethods for AccessKeyUsage {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.public_key);
+        args.add(&self.account_id);
+        args.add(&self.last_used_block_height);
+        args.add(&self.last_used_transaction_hash);
+        args.add(&self.last_nonce);
+    }
+
+    // The reprocessed blocks do not move the usage back
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("UPDATE access_keys
+            SET last_used_block_height = usages.last_used_block_height,
+                last_used_transaction_hash = usages.last_used_transaction_hash,
+                last_nonce = usages.last_nonce
+            FROM (VALUES "
+            .to_owned()
+            + &crate::models::create_placeholders(items_count, AccessKeyUsage::field_count())?
+            + ") AS usages (public_key, account_id, last_used_block_height, last_used_transaction_hash, last_nonce)
+            WHERE access_keys.public_key = usages.public_key
+                AND access_keys.account_id = usages.account_id
+                AND (access_keys.last_nonce IS NULL OR access_keys.last_nonce < usages.last_nonce)")
+    }
+
+    fn name() -> String {
+        "access_keys usages".to_string()
+    }
+}
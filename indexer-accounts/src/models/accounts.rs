@@ -3,6 +3,7 @@ use sqlx::Arguments;
 
 use near_lake_framework::near_indexer_primitives;
 
+use crate::account_kind::AccountKind;
 use crate::models::FieldCount;
 
 #[derive(Debug, Clone, sqlx::FromRow, FieldCount)]
@@ -12,20 +13,49 @@ pub struct Account {
     pub deleted_by_receipt_id: Option<String>,
     pub created_by_block_height: BigDecimal,
     pub deleted_by_block_height: Option<BigDecimal>,
+    pub account_kind: String,
+    // The predecessor of the creating receipt, NULL for genesis accounts
+    pub created_by_account_id: Option<String>,
+    pub parent_account_id: Option<String>,
+    pub deletion_beneficiary_id: Option<String>,
 }
 
 impl Account {
+    pub fn new_from_genesis(
+        account_id: &near_indexer_primitives::types::AccountId,
+        genesis_height: near_indexer_primitives::types::BlockHeight,
+    ) -> Self {
+        let account_kind = AccountKind::from_account_id(account_id);
+        Self {
+            account_id: account_id.to_string(),
+            created_by_receipt_id: None,
+            deleted_by_receipt_id: None,
+            created_by_block_height: BigDecimal::from(genesis_height),
+            deleted_by_block_height: None,
+            account_kind: account_kind.as_str().to_string(),
+            created_by_account_id: None,
+            parent_account_id: account_kind.parent_account_id(account_id),
+            deletion_beneficiary_id: None,
+        }
+    }
+
     pub fn new_from_receipt(
         account_id: &near_indexer_primitives::types::AccountId,
         created_by_receipt_id: &near_indexer_primitives::CryptoHash,
+        created_by_account_id: &near_indexer_primitives::types::AccountId,
         created_by_block_height: near_indexer_primitives::types::BlockHeight,
     ) -> Self {
+        let account_kind = AccountKind::from_account_id(account_id);
         Self {
             account_id: account_id.to_string(),
             created_by_receipt_id: Some(created_by_receipt_id.to_string()),
             deleted_by_receipt_id: None,
             created_by_block_height: BigDecimal::from(created_by_block_height),
             deleted_by_block_height: None,
+            account_kind: account_kind.as_str().to_string(),
+            created_by_account_id: Some(created_by_account_id.to_string()),
+            parent_account_id: account_kind.parent_account_id(account_id),
+            deletion_beneficiary_id: None,
         }
     }
 }
@@ -37,6 +67,10 @@ impl crate::models::MySqlMethods for Account {
         args.add(&self.deleted_by_receipt_id);
         args.add(&self.created_by_block_height);
         args.add(&self.deleted_by_block_height);
+        args.add(&self.account_kind);
+        args.add(&self.created_by_account_id);
+        args.add(&self.parent_account_id);
+        args.add(&self.deletion_beneficiary_id);
     }
 
     fn insert_query(items_count: usize) -> anyhow::Result<String> {
@@ -0,0 +1,39 @@
+use bigdecimal::BigDecimal;
+use sqlx::Arguments;
+
+use crate::models::FieldCount;
+
+/// The mismatch between the stored state and JSON RPC found by `reconcile`
+#[derive(Debug, sqlx::FromRow, FieldCount)]
+pub struct AccountsInconsistency {
+    pub checked_at_block_height: BigDecimal,
+    pub account_id: String,
+    // Empty for the account mismatches
+    pub public_key: String,
+    // ACCOUNT_NOT_FOUND, ACCESS_KEY_NOT_FOUND (alive in DB, absent in RPC) or ACCESS_KEY_MISSING (absent in DB)
+    pub inconsistency_kind: String,
+    pub is_fixed: bool,
+}
+
+impl crate::models::MySqlMethods for AccountsInconsistency {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.checked_at_block_height);
+        args.add(&self.account_id);
+        args.add(&self.public_key);
+        args.add(&self.inconsistency_kind);
+        args.add(&self.is_fixed);
+    }
+
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO accounts_inconsistencies VALUES ".to_owned()
+            + &crate::models::create_placeholders(
+                items_count,
+                AccountsInconsistency::field_count(),
+            )?
+            + " ON CONFLICT DO NOTHING")
+    }
+
+    fn name() -> String {
+        "accounts_inconsistencies".to_string()
+    }
+}
@@ -1,12 +1,12 @@
 use futures::future::try_join_all;
 use near_lake_framework::near_indexer_primitives::views::AccessKeyPermissionView;
-use sqlx::Arguments;
 use std::fmt::Write;
 
 pub use indexer_accounts::FieldCount;
 
 pub(crate) mod access_keys;
 pub(crate) mod accounts;
+pub(crate) mod accounts_inconsistencies;
 
 pub trait FieldCount {
     /// Get the number of fields on a struct.
@@ -73,93 +73,6 @@ async fn insert_retry_or_panic<T: MySqlMethods + std::fmt::Debug>(
     Ok(())
 }
 
-// todo it would be great to control how many lines we've updated
-pub(crate) async fn update_retry_or_panic<T: MySqlMethods + std::fmt::Debug>(
-    pool: &sqlx::Pool<sqlx::Postgres>,
-    query: &str,
-    items: &[T],
-    retry_count: usize,
-) -> anyhow::Result<()> {
-    for item in items {
-        let mut interval = crate::INTERVAL;
-        let mut retry_attempt = 0usize;
-
-        loop {
-            if retry_attempt == retry_count {
-                return Err(anyhow::anyhow!(
-                    "Failed to perform query to database after {} attempts. Stop trying.",
-                    retry_count
-                ));
-            }
-            retry_attempt += 1;
-
-            let mut args = sqlx::postgres::PgArguments::default();
-            item.add_to_args(&mut args);
-
-            match sqlx::query_with(query, args).execute(pool).await {
-                Ok(_) => break,
-                Err(async_error) => {
-                    eprintln!(
-                        "Error occurred during {}:\n{} were not updated. \n{:#?} \n Retrying in {} milliseconds...",
-                        async_error,
-                        &T::name(),
-                        &items,
-                        interval.as_millis(),
-                    );
-                    tokio::time::sleep(interval).await;
-                    if interval < crate::MAX_DELAY_TIME {
-                        interval *= 2;
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
-pub(crate) async fn select_retry_or_panic(
-    pool: &sqlx::Pool<sqlx::Postgres>,
-    query: &str,
-    substitution_items: &[String],
-    retry_count: usize,
-) -> anyhow::Result<Vec<sqlx::postgres::PgRow>> {
-    let mut interval = crate::INTERVAL;
-    let mut retry_attempt = 0usize;
-
-    loop {
-        if retry_attempt == retry_count {
-            return Err(anyhow::anyhow!(
-                "Failed to perform query to database after {} attempts. Stop trying.",
-                retry_count
-            ));
-        }
-        retry_attempt += 1;
-
-        let mut args = sqlx::postgres::PgArguments::default();
-        for item in substitution_items {
-            args.add(item);
-        }
-
-        match sqlx::query_with(query, args).fetch_all(pool).await {
-            Ok(res) => return Ok(res),
-            Err(async_error) => {
-                // todo we print here select with non-filled placeholders. It would be better to get the final select statement here
-                tracing::error!(
-                         target: crate::LOGGING_PREFIX,
-                         "Error occurred during {}:\nFailed SELECT:\n{}\n Retrying in {} milliseconds...",
-                         async_error,
-                    query,
-                         interval.as_millis(),
-                     );
-                tokio::time::sleep(interval).await;
-                if interval < crate::MAX_DELAY_TIME {
-                    interval *= 2;
-                }
-            }
-        }
-    }
-}
-
 // Generates `($1, $2), ($3, $4)`
 pub(crate) fn create_placeholders(
     mut items_count: usize,
@@ -0,0 +1,179 @@
+use bigdecimal::BigDecimal;
+
+/// The account as it was after the given block
+#[derive(Debug, serde::Serialize, sqlx::FromRow)]
+pub(crate) struct AccountAtBlock {
+    pub account_id: String,
+    pub account_kind: String,
+    pub created_by_receipt_id: Option<String>,
+    pub created_by_account_id: Option<String>,
+    pub created_by_block_height: BigDecimal,
+    // Filled if the account is deleted later
+    pub deleted_by_block_height: Option<BigDecimal>,
+    pub parent_account_id: Option<String>,
+}
+
+/// The access key which could sign for the account after the given block
+#[derive(Debug, serde::Serialize, sqlx::FromRow)]
+pub(crate) struct AccessKeyAtBlock {
+    pub public_key: String,
+    pub permission_kind: String,
+    pub nonce: Option<BigDecimal>,
+    pub allowance: Option<BigDecimal>,
+    pub receiver_id: Option<String>,
+    pub method_names: Option<Vec<String>>,
+    pub created_by_receipt_id: Option<String>,
+    pub created_by_block_height: BigDecimal,
+    // Filled if the key is deleted later
+    pub deleted_by_block_height: Option<BigDecimal>,
+}
+
+/// Returns the account if it exists after the block `block_height`
+pub(crate) async fn account_at_block(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &str,
+    block_height: u64,
+) -> anyhow::Result<Option<AccountAtBlock>> {
+    Ok(sqlx::query_as::<_, AccountAtBlock>(
+        r"SELECT account_id, account_kind, created_by_receipt_id, created_by_account_id,
+                 created_by_block_height, deleted_by_block_height, parent_account_id
+          FROM accounts
+          WHERE account_id = $1
+              AND created_by_block_height <= $2::numeric(20, 0)
+              AND (deleted_by_block_height IS NULL OR deleted_by_block_height > $2::numeric(20, 0))
+          ORDER BY created_by_block_height DESC
+          LIMIT 1",
+    )
+    .bind(account_id)
+    .bind(BigDecimal::from(block_height))
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Returns the access keys of the account which exist after the block `block_height`,
+/// or the current keys if `block_height` is not given
+pub(crate) async fn access_keys_at_block(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &str,
+    block_height: Option<u64>,
+) -> anyhow::Result<Vec<AccessKeyAtBlock>> {
+    Ok(sqlx::query_as::<_, AccessKeyAtBlock>(
+        r"SELECT public_key, permission_kind, nonce, allowance, receiver_id, method_names,
+                 created_by_receipt_id, created_by_block_height, deleted_by_block_height
+          FROM access_keys
+          WHERE account_id = $1
+              AND ($2::numeric(20, 0) IS NULL OR created_by_block_height <= $2::numeric(20, 0))
+              AND (deleted_by_block_height IS NULL OR deleted_by_block_height > $2::numeric(20, 0))
+          ORDER BY created_by_block_height, public_key",
+    )
+    .bind(account_id)
+    .bind(block_height.map(BigDecimal::from))
+    .fetch_all(pool)
+    .await?)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bigdecimal::ToPrimitive;
+
+    use super::*;
+
+    /// Connects to the migrated database in `TEST_DATABASE_URL`, the test is skipped without it
+    pub(crate) async fn test_pool() -> anyhow::Result<Option<sqlx::Pool<sqlx::Postgres>>> {
+        match std::env::var("TEST_DATABASE_URL") {
+            Ok(database_url) => Ok(Some(sqlx::PgPool::connect(&database_url).await?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Stores the account which lives in the blocks 10..20, is recreated in 20 and deleted in 30,
+    /// and is created again in 40. Its keys: `a` lives in 10..20, `b` is added in 15, `c` in 25.
+    /// Returns the unique account id, the rows should be removed with [remove_history]
+    pub(crate) async fn store_history(pool: &sqlx::Pool<sqlx::Postgres>) -> anyhow::Result<String> {
+        let account_id = format!(
+            "history-{}.test.near",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_nanos()
+        );
+        sqlx::query(
+            r"INSERT INTO accounts (account_id, account_kind, created_by_block_height, deleted_by_block_height)
+              VALUES ($1, 'NAMED', 10, 20), ($1, 'NAMED', 20, 30), ($1, 'NAMED', 40, NULL)",
+        )
+        .bind(&account_id)
+        .execute(pool)
+        .await?;
+        sqlx::query(
+            r"INSERT INTO access_keys (public_key, account_id, permission_kind, created_by_block_height, deleted_by_block_height)
+              VALUES ('a', $1, 'FULL_ACCESS', 10, 20), ('b', $1, 'FULL_ACCESS', 15, NULL), ('c', $1, 'FULL_ACCESS', 25, NULL)",
+        )
+        .bind(&account_id)
+        .execute(pool)
+        .await?;
+        Ok(account_id)
+    }
+
+    pub(crate) async fn remove_history(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        account_id: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM access_keys WHERE account_id = $1")
+            .bind(account_id)
+            .execute(pool)
+            .await?;
+        sqlx::query("DELETE FROM accounts WHERE account_id = $1")
+            .bind(account_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn account_at_block_takes_the_alive_incarnation() -> anyhow::Result<()> {
+        let pool = match test_pool().await? {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        let account_id = store_history(&pool).await?;
+
+        let mut created_by_block_heights = vec![];
+        for block_height in [5, 15, 20, 25, 30, 35, 45] {
+            created_by_block_heights.push(
+                account_at_block(&pool, &account_id, block_height)
+                    .await?
+                    .and_then(|account| account.created_by_block_height.to_u64()),
+            );
+        }
+        remove_history(&pool, &account_id).await?;
+
+        assert_eq!(
+            created_by_block_heights,
+            vec![None, Some(10), Some(20), Some(20), None, None, Some(40)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn access_keys_at_block_without_height_returns_the_current_keys() -> anyhow::Result<()> {
+        let pool = match test_pool().await? {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        let account_id = store_history(&pool).await?;
+
+        let public_keys = |keys: Vec<AccessKeyAtBlock>| {
+            keys.into_iter()
+                .map(|key| key.public_key)
+                .collect::<Vec<_>>()
+        };
+        let current = public_keys(access_keys_at_block(&pool, &account_id, None).await?);
+        let at_17 = public_keys(access_keys_at_block(&pool, &account_id, Some(17)).await?);
+        let at_20 = public_keys(access_keys_at_block(&pool, &account_id, Some(20)).await?);
+        remove_history(&pool, &account_id).await?;
+
+        assert_eq!(current, vec!["b", "c"]);
+        assert_eq!(at_17, vec!["a", "b"]);
+        assert_eq!(at_20, vec!["b"]);
+        Ok(())
+    }
+}
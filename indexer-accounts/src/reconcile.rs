@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use futures::{StreamExt, TryStreamExt};
+use near_lake_framework::near_indexer_primitives;
+use sqlx::Row;
+
+use crate::db_adapters::mutations::Mutation;
+use crate::models;
+
+const RPC_CONCURRENCY: usize = 10;
+
+/// What is found for the account: the mismatches, each with the mutations fixing it
+#[derive(Debug, Default)]
+struct AccountCheck {
+    inconsistencies: Vec<(
+        models::accounts_inconsistencies::AccountsInconsistency,
+        Vec<Mutation>,
+    )>,
+}
+
+/// Checks the random sample of the accounts alive at the block against JSON RPC,
+/// stores the mismatches to `accounts_inconsistencies` and optionally fixes them
+pub(crate) async fn run(
+    opts: &indexer_opts::Opts,
+    args: &indexer_opts::ReconcileArgs,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<()> {
+    // The fixes are written to the tables of the indexer, so they must not race the running instance
+    let _lock = if args.auto_fix {
+        Some(opts.lock_indexer(pool).await?)
+    } else {
+        None
+    };
+    let block_height = opts.reconcile_block_height(args, pool).await?;
+    let rpc_url = opts
+        .rpc_url
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("`rpc-url` must be provided for `reconcile`"))?;
+    let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(rpc_url);
+
+    let account_ids: Vec<String> = sqlx::query(
+        r"SELECT account_id FROM accounts
+          WHERE created_by_block_height <= $1::numeric(20, 0)
+              AND (deleted_by_block_height IS NULL OR deleted_by_block_height > $1::numeric(20, 0))
+          ORDER BY random()
+          LIMIT $2",
+    )
+    .bind(BigDecimal::from(block_height))
+    .bind(i64::from(args.sample_size))
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(|row| row.get(0))
+    .collect();
+    tracing::info!(
+        target: crate::LOGGING_PREFIX,
+        "Reconciling {} accounts at block {}",
+        account_ids.len(),
+        block_height
+    );
+
+    let checks: Vec<AccountCheck> = futures::stream::iter(account_ids.iter())
+        .map(|account_id| check_account(pool, &json_rpc_client, account_id, block_height))
+        .buffer_unordered(RPC_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let (mut inconsistencies, fixes): (Vec<_>, Vec<_>) = checks
+        .into_iter()
+        .flat_map(|check| check.inconsistencies)
+        .unzip();
+    if args.auto_fix {
+        let fixes_count: Vec<usize> = fixes.iter().map(Vec::len).collect();
+        let mutations: Vec<Mutation> = fixes.into_iter().flatten().collect();
+        if !mutations.is_empty() {
+            let mut rows_affected =
+                crate::db_adapters::mutations::apply_retry_or_panic(pool, &mutations, 10)
+                    .await?
+                    .into_iter();
+            // The fix could change nothing, e.g. the account is already marked as deleted
+            for (inconsistency, fixes_count) in inconsistencies.iter_mut().zip(fixes_count) {
+                inconsistency.is_fixed = rows_affected
+                    .by_ref()
+                    .take(fixes_count)
+                    .filter(|rows| *rows > 0)
+                    .count()
+                    > 0;
+            }
+        }
+    }
+    models::chunked_insert(pool, &inconsistencies, 10).await?;
+
+    tracing::info!(
+        target: crate::LOGGING_PREFIX,
+        "Found {} inconsistencies in {} accounts at block {}, fixed {}",
+        inconsistencies.len(),
+        account_ids.len(),
+        block_height,
+        inconsistencies
+            .iter()
+            .filter(|inconsistency| inconsistency.is_fixed)
+            .count()
+    );
+    Ok(())
+}
+
+async fn check_account(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    json_rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    account_id: &str,
+    block_height: u64,
+) -> anyhow::Result<AccountCheck> {
+    let account_id = near_indexer_primitives::types::AccountId::from_str(account_id)?;
+    let rpc_keys = rpc_access_keys(json_rpc_client, &account_id, block_height).await?;
+    let db_keys: HashSet<String> = match rpc_keys {
+        // The keys are not compared for the account which does not exist
+        Some(_) => {
+            crate::queries::access_keys_at_block(pool, account_id.as_str(), Some(block_height))
+                .await?
+                .into_iter()
+                .map(|key| key.public_key)
+                .collect()
+        }
+        None => HashSet::new(),
+    };
+    Ok(classify(&account_id, block_height, rpc_keys, &db_keys))
+}
+
+/// The access keys of the account at the block by JSON RPC, None if the account does not exist
+async fn rpc_access_keys(
+    json_rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    account_id: &near_indexer_primitives::types::AccountId,
+    block_height: u64,
+) -> anyhow::Result<Option<HashMap<String, near_indexer_primitives::views::AccessKeyInfoView>>> {
+    let block_reference = near_primitives::types::BlockReference::BlockId(
+        near_primitives::types::BlockId::Height(block_height),
+    );
+
+    let account_response = json_rpc_client
+        .call(near_jsonrpc_client::methods::query::RpcQueryRequest {
+            block_reference: block_reference.clone(),
+            request: near_primitives::views::QueryRequest::ViewAccount {
+                account_id: account_id.clone(),
+            },
+        })
+        .await;
+    if let Err(err) = account_response {
+        return match err.handler_error() {
+            Some(near_jsonrpc_primitives::types::query::RpcQueryError::UnknownAccount {
+                ..
+            }) => Ok(None),
+            _ => Err(err.into()),
+        };
+    }
+
+    let keys_response = json_rpc_client
+        .call(near_jsonrpc_client::methods::query::RpcQueryRequest {
+            block_reference,
+            request: near_primitives::views::QueryRequest::ViewAccessKeyList {
+                account_id: account_id.clone(),
+            },
+        })
+        .await?;
+    match keys_response.kind {
+        near_jsonrpc_primitives::types::query::QueryResponseKind::AccessKeyList(list) => Ok(Some(
+            list.keys
+                .into_iter()
+                .map(|key| (key.public_key.to_string(), key))
+                .collect(),
+        )),
+        _ => unreachable!(
+            "Unreachable code! Asked for ViewAccessKeyList (block_height {}, account_id {})\n\
+            Received unexpected response kind\nReport this to https://github.com/near/near-jsonrpc-client-rs",
+            block_height,
+            account_id,
+        ),
+    }
+}
+
+/// Compares the state from JSON RPC (None if the account does not exist) with the keys stored in the DB
+fn classify(
+    account_id: &near_indexer_primitives::types::AccountId,
+    block_height: u64,
+    rpc_keys: Option<HashMap<String, near_indexer_primitives::views::AccessKeyInfoView>>,
+    db_keys: &HashSet<String>,
+) -> AccountCheck {
+    let mut check = AccountCheck::default();
+    let rpc_keys = match rpc_keys {
+        Some(rpc_keys) => rpc_keys,
+        None => {
+            check.inconsistencies.push((
+                inconsistency(block_height, account_id, "", "ACCOUNT_NOT_FOUND"),
+                vec![
+                    Mutation::DeleteAccount(account_to_delete(account_id, block_height)),
+                    Mutation::DeleteAccountAccessKeys(access_key_to_delete(
+                        "",
+                        account_id,
+                        block_height,
+                    )),
+                ],
+            ));
+            return check;
+        }
+    };
+
+    for public_key in db_keys.iter().filter(|key| !rpc_keys.contains_key(*key)) {
+        check.inconsistencies.push((
+            inconsistency(block_height, account_id, public_key, "ACCESS_KEY_NOT_FOUND"),
+            vec![Mutation::DeleteAccessKey(access_key_to_delete(
+                public_key,
+                account_id,
+                block_height,
+            ))],
+        ));
+    }
+    for (public_key, key) in rpc_keys.iter().filter(|(key, _)| !db_keys.contains(*key)) {
+        // The key could be stored as deleted before the block, so the row is restored, not just inserted
+        check.inconsistencies.push((
+            inconsistency(block_height, account_id, public_key, "ACCESS_KEY_MISSING"),
+            vec![Mutation::RestoreAccessKey(
+                models::access_keys::AccessKey::without_receipt(
+                    &key.public_key,
+                    account_id,
+                    &key.access_key,
+                    block_height,
+                ),
+            )],
+        ));
+    }
+    check
+}
+
+fn inconsistency(
+    block_height: u64,
+    account_id: &near_indexer_primitives::types::AccountId,
+    public_key: &str,
+    inconsistency_kind: &str,
+) -> models::accounts_inconsistencies::AccountsInconsistency {
+    models::accounts_inconsistencies::AccountsInconsistency {
+        checked_at_block_height: BigDecimal::from(block_height),
+        account_id: account_id.to_string(),
+        public_key: public_key.to_string(),
+        inconsistency_kind: inconsistency_kind.to_string(),
+        is_fixed: false,
+    }
+}
+
+// The exact block of the deletion is unknown, the checked block is used as the upper bound
+fn account_to_delete(
+    account_id: &near_indexer_primitives::types::AccountId,
+    block_height: u64,
+) -> models::accounts::Account {
+    let account_kind = crate::account_kind::AccountKind::from_account_id(account_id);
+    models::accounts::Account {
+        account_id: account_id.to_string(),
+        created_by_receipt_id: None,
+        deleted_by_receipt_id: None,
+        created_by_block_height: Default::default(),
+        deleted_by_block_height: Some(BigDecimal::from(block_height)),
+        account_kind: account_kind.as_str().to_string(),
+        created_by_account_id: None,
+        parent_account_id: account_kind.parent_account_id(account_id),
+        deletion_beneficiary_id: None,
+    }
+}
+
+fn access_key_to_delete(
+    public_key: &str,
+    account_id: &near_indexer_primitives::types::AccountId,
+    block_height: u64,
+) -> models::access_keys::AccessKey {
+    models::access_keys::AccessKey {
+        public_key: public_key.to_string(),
+        account_id: account_id.to_string(),
+        created_by_receipt_id: None,
+        deleted_by_receipt_id: None,
+        created_by_block_height: Default::default(),
+        deleted_by_block_height: Some(BigDecimal::from(block_height)),
+        permission_kind: "".to_string(),
+        nonce: None,
+        allowance: None,
+        receiver_id: None,
+        method_names: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_HEIGHT: u64 = 100;
+
+    fn public_key(seed: &str) -> String {
+        near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, seed)
+            .public_key()
+            .to_string()
+    }
+
+    /// JSON RPC node which knows `alice.near` with the keys `a` and `c`, any other account does not exist
+    async fn mock_rpc(request: actix_web::web::Json<serde_json::Value>) -> actix_web::HttpResponse {
+        let block_hash = near_primitives::hash::hash(b"block").to_string();
+        let params = &request["params"];
+        let account_id = params["account_id"].as_str().unwrap_or_default();
+        let mut response = match (params["request_type"].as_str(), account_id) {
+            (Some("view_account"), "alice.near") => serde_json::json!({
+                "result": {
+                    "amount": "1",
+                    "locked": "0",
+                    "code_hash": "11111111111111111111111111111111",
+                    "storage_usage": 100,
+                    "storage_paid_at": 0,
+                    "block_height": BLOCK_HEIGHT,
+                    "block_hash": block_hash
+                }
+            }),
+            (Some("view_access_key_list"), "alice.near") => serde_json::json!({
+                "result": {
+                    "keys": [
+                        {"public_key": public_key("a"), "access_key": {"nonce": 0, "permission": "FullAccess"}},
+                        {"public_key": public_key("c"), "access_key": {"nonce": 0, "permission": "FullAccess"}}
+                    ],
+                    "block_height": BLOCK_HEIGHT,
+                    "block_hash": block_hash
+                }
+            }),
+            _ => serde_json::json!({
+                "error": {
+                    "name": "HANDLER_ERROR",
+                    "cause": {
+                        "name": "UNKNOWN_ACCOUNT",
+                        "info": {
+                            "requested_account_id": account_id,
+                            "block_height": BLOCK_HEIGHT,
+                            "block_hash": block_hash
+                        }
+                    },
+                    "code": -32000,
+                    "message": "Server error",
+                    "data": format!("account {} does not exist while viewing", account_id)
+                }
+            }),
+        };
+        response["jsonrpc"] = serde_json::json!("2.0");
+        response["id"] = request["id"].clone();
+        actix_web::HttpResponse::Ok().json(response)
+    }
+
+    async fn check_with_mock_rpc(
+        account_id: &str,
+        db_keys: &[&str],
+    ) -> anyhow::Result<(Vec<(String, String)>, Vec<&'static str>)> {
+        let server = actix_web::HttpServer::new(|| {
+            actix_web::App::new().route("/", actix_web::web::post().to(mock_rpc))
+        })
+        .bind(("127.0.0.1", 0))?;
+        let rpc_url = format!("http://{}", server.addrs()[0]);
+        let server = server.run();
+        let server_handle = server.handle();
+        tokio::spawn(server);
+
+        let account_id = near_indexer_primitives::types::AccountId::from_str(account_id)?;
+        let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(&rpc_url);
+        let rpc_keys = rpc_access_keys(&json_rpc_client, &account_id, BLOCK_HEIGHT).await;
+        server_handle.stop(false).await;
+
+        let db_keys: HashSet<String> = db_keys.iter().map(|seed| public_key(seed)).collect();
+        let check = classify(&account_id, BLOCK_HEIGHT, rpc_keys?, &db_keys);
+        let mut inconsistencies: Vec<(String, String)> = vec![];
+        let mut fixes: Vec<&'static str> = vec![];
+        for (inconsistency, inconsistency_fixes) in check.inconsistencies {
+            inconsistencies.push((inconsistency.inconsistency_kind, inconsistency.public_key));
+            fixes.extend(inconsistency_fixes.iter().map(|fix| match fix {
+                Mutation::CreateAccount(_) => "CreateAccount",
+                Mutation::CreateImplicitAccount(_) => "CreateImplicitAccount",
+                Mutation::DeleteAccount(_) => "DeleteAccount",
+                Mutation::CreateAccessKey(_) => "CreateAccessKey",
+                Mutation::RestoreAccessKey(_) => "RestoreAccessKey",
+                Mutation::DeleteAccessKey(_) => "DeleteAccessKey",
+                Mutation::DeleteAccountAccessKeys(_) => "DeleteAccountAccessKeys",
+            }));
+        }
+        inconsistencies.sort();
+        fixes.sort_unstable();
+        Ok((inconsistencies, fixes))
+    }
+
+    #[tokio::test]
+    async fn classifies_unknown_account() -> anyhow::Result<()> {
+        let (inconsistencies, fixes) = check_with_mock_rpc("bob.near", &["a"]).await?;
+        assert_eq!(
+            inconsistencies,
+            vec![("ACCOUNT_NOT_FOUND".to_string(), "".to_string())]
+        );
+        assert_eq!(fixes, vec!["DeleteAccount", "DeleteAccountAccessKeys"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn classifies_missing_and_extra_keys() -> anyhow::Result<()> {
+        // `a` is in both, `b` is only in the DB, `c` is only in RPC
+        let (inconsistencies, fixes) = check_with_mock_rpc("alice.near", &["a", "b"]).await?;
+        assert_eq!(
+            inconsistencies,
+            vec![
+                ("ACCESS_KEY_MISSING".to_string(), public_key("c")),
+                ("ACCESS_KEY_NOT_FOUND".to_string(), public_key("b")),
+            ]
+        );
+        assert_eq!(fixes, vec!["DeleteAccessKey", "RestoreAccessKey"]);
+        Ok(())
+    }
+}
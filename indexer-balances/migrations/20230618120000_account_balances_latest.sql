@@ -0,0 +1,12 @@
+CREATE TABLE account_balances_latest
+(
+    account_id   text           PRIMARY KEY,
+    non_staked   numeric(40, 0) NOT NULL,
+    staked       numeric(40, 0) NOT NULL,
+    block_height numeric(20, 0) NOT NULL,
+    event_index  numeric(38, 0) NOT NULL
+);
+
+-- There is no backfill here: the DISTINCT ON over the whole near_balance_events would block the indexer.
+-- The account without the row is looked up in near_balance_events, and the found balance is stored here,
+-- so the table is filled lazily while the indexer is working
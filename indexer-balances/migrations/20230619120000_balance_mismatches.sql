@@ -0,0 +1,13 @@
+CREATE TABLE balance_mismatches
+(
+    block_height    numeric(20, 0) NOT NULL,
+    block_timestamp numeric(20, 0) NOT NULL,
+    account_id      text           NOT NULL,
+    db_non_staked   numeric(40, 0) NOT NULL,
+    db_staked       numeric(40, 0) NOT NULL,
+    rpc_non_staked  numeric(40, 0) NOT NULL,
+    rpc_staked      numeric(40, 0) NOT NULL,
+    PRIMARY KEY (account_id, block_height)
+);
+
+CREATE INDEX CONCURRENTLY balance_mismatches_block_height_idx ON balance_mismatches (block_height);
@@ -0,0 +1,53 @@
+use crate::models::{balance_changes::NearBalanceEvent, SqlxMethods};
+
+/// Reports the holes in `near_balance_events` table.
+/// The expected block heights are taken either from NEAR Lake
+/// (the blocks which change at least one balance) or from `blocks` table of `indexer-base`.
+pub(crate) async fn run(
+    opts: &indexer_opts::Opts,
+    args: &indexer_opts::audit::AuditArgs,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<()> {
+    let (from_block_height, to_block_height) = opts.audit_range(args, pool).await?;
+    let source = opts.audit_source(args, pool).await?;
+    let table = NearBalanceEvent::name();
+    let mut reporter = indexer_opts::audit::HoleReporter::new(&opts.indexer_id, &table);
+
+    for (window_start, window_end) in
+        indexer_opts::audit::audit_windows(from_block_height, to_block_height)
+    {
+        let expected = match source {
+            indexer_opts::audit::AuditSource::Lake => opts
+                .expected_block_heights_from_lake(
+                    window_start,
+                    window_end,
+                    |streamer_message| async move {
+                        Ok(
+                            if crate::db_adapters::balance_changes::has_balance_changes(
+                                &streamer_message.shards,
+                            ) {
+                                vec![NearBalanceEvent::name()]
+                            } else {
+                                vec![]
+                            },
+                        )
+                    },
+                )
+                .await?
+                .remove(&table)
+                .unwrap_or_default(),
+            _ => {
+                indexer_opts::audit::expected_block_heights_from_blocks(
+                    pool,
+                    window_start,
+                    window_end,
+                )
+                .await?
+            }
+        };
+        let stored = indexer_opts::audit::stored_block_heights(pool, &table, &expected).await?;
+        reporter.push_window(&expected, &stored)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
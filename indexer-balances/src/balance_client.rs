@@ -1,7 +1,13 @@
 use std::str::FromStr;
 
+use bigdecimal::BigDecimal;
+use cached::{Cached, SizedCache};
+
+use crate::metrics;
+use crate::models::account_balances_latest::AccountBalanceLatest;
 use crate::models::balance_changes::NearBalanceEvent;
-use crate::models::SqlxMethods;
+use crate::models::balance_mismatches::BalanceMismatch;
+use crate::models::SelectPrevBalance;
 use async_trait::async_trait;
 use near_lake_framework::near_indexer_primitives;
 
@@ -91,17 +97,51 @@ impl BalanceClient for PgBalanceClient {
         account_id: &near_indexer_primitives::types::AccountId,
         block_header: &near_indexer_primitives::views::BlockHeaderView,
     ) -> anyhow::Result<crate::BalanceDetails> {
-        let account_balance = match crate::models::select_one_retry_or_panic(
+        // The latest balance is the balance before the block only if it was changed before the block.
+        // Otherwise (the block is reindexed, or the other shard of this block is already stored),
+        // we have to look for the previous event in near_balance_events
+        let latest_balance: Option<AccountBalanceLatest> =
+            crate::models::select_one_retry_or_panic(
+                &self.pool,
+                &AccountBalanceLatest::select_prev_balance_query(block_header.height, account_id),
+                crate::RETRY_COUNT,
+            )
+            .await?;
+        let is_backfilled = latest_balance.is_some();
+        if let Some(latest_balance) = latest_balance {
+            if latest_balance.block_height < BigDecimal::from(block_header.height) {
+                return Ok(crate::BalanceDetails {
+                    non_staked: u128::from_str(&latest_balance.non_staked.to_string())?,
+                    staked: u128::from_str(&latest_balance.staked.to_string())?,
+                });
+            }
+        }
+
+        let account_balance = match crate::models::select_one_retry_or_panic::<NearBalanceEvent>(
             &self.pool,
             &NearBalanceEvent::select_prev_balance_query(block_header.height, account_id),
             crate::RETRY_COUNT,
         )
         .await
         {
-            Ok(Some(balance_event)) => crate::BalanceDetails {
-                non_staked: u128::from_str(&balance_event.absolute_nonstaked_amount.to_string())?,
-                staked: u128::from_str(&balance_event.absolute_staked_amount.to_string())?,
-            },
+            Ok(Some(balance_event)) => {
+                // The accounts last changed before `account_balances_latest` was created get their row
+                // on the first lookup, so the next lookups do not touch near_balance_events
+                if !is_backfilled {
+                    crate::models::chunked_insert(
+                        &self.pool,
+                        &[AccountBalanceLatest::from_event(&balance_event)],
+                        crate::RETRY_COUNT,
+                    )
+                    .await?;
+                }
+                crate::BalanceDetails {
+                    non_staked: u128::from_str(
+                        &balance_event.absolute_nonstaked_amount.to_string(),
+                    )?,
+                    staked: u128::from_str(&balance_event.absolute_staked_amount.to_string())?,
+                }
+            }
             Ok(None) => crate::BalanceDetails {
                 non_staked: 0,
                 staked: 0,
@@ -112,3 +152,272 @@ impl BalanceClient for PgBalanceClient {
         Ok(account_balance)
     }
 }
+
+// The mismatches are rare, the queue is needed only to keep the inserts off the lookups
+const MISMATCHES_QUEUE_SIZE: usize = 1000;
+
+/// Takes the balance from the DB, but verifies every `check_every`-th lookup and the first lookup
+/// of each account after the start against JSON RPC.
+/// If the balances differ, the RPC one is used, so the wrong delta does not propagate further.
+/// The mismatches are written to `balance_mismatches` in the background
+pub struct HybridBalanceClient {
+    db_client: Box<dyn BalanceClient + Send + Sync>,
+    rpc_client: JsonRpcBalanceClient,
+    check_every: u64,
+    lookups_count: std::sync::atomic::AtomicU64,
+    // Bounded like `BalanceCache`, the evicted account is checked again on its next lookup
+    seen_accounts: tokio::sync::Mutex<SizedCache<near_indexer_primitives::types::AccountId, ()>>,
+    mismatches_sender: tokio::sync::mpsc::Sender<BalanceMismatch>,
+}
+
+impl HybridBalanceClient {
+    pub fn new(
+        pool: sqlx::Pool<sqlx::Postgres>,
+        json_rpc_client: near_jsonrpc_client::JsonRpcClient,
+        check_every: u64,
+    ) -> Self {
+        let (mismatches_sender, mismatches_receiver) =
+            tokio::sync::mpsc::channel(MISMATCHES_QUEUE_SIZE);
+        tokio::spawn(store_mismatches(pool.clone(), mismatches_receiver));
+        Self::with_clients(
+            Box::new(PgBalanceClient::new(pool)),
+            JsonRpcBalanceClient::new(json_rpc_client),
+            check_every,
+            mismatches_sender,
+        )
+    }
+
+    fn with_clients(
+        db_client: Box<dyn BalanceClient + Send + Sync>,
+        rpc_client: JsonRpcBalanceClient,
+        check_every: u64,
+        mismatches_sender: tokio::sync::mpsc::Sender<BalanceMismatch>,
+    ) -> Self {
+        Self {
+            db_client,
+            rpc_client,
+            check_every,
+            lookups_count: std::sync::atomic::AtomicU64::new(0),
+            seen_accounts: tokio::sync::Mutex::new(SizedCache::with_size(100_000)),
+            mismatches_sender,
+        }
+    }
+
+    async fn should_check(&self, account_id: &near_indexer_primitives::types::AccountId) -> bool {
+        let lookup = self
+            .lookups_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let first_seen = self
+            .seen_accounts
+            .lock()
+            .await
+            .cache_set(account_id.clone(), ())
+            .is_none();
+        first_seen || (self.check_every != 0 && lookup % self.check_every == 0)
+    }
+}
+
+#[async_trait]
+impl BalanceClient for HybridBalanceClient {
+    async fn get_balance_before_block(
+        &self,
+        account_id: &near_indexer_primitives::types::AccountId,
+        block_header: &near_indexer_primitives::views::BlockHeaderView,
+    ) -> anyhow::Result<crate::BalanceDetails> {
+        if !self.should_check(account_id).await {
+            return self
+                .db_client
+                .get_balance_before_block(account_id, block_header)
+                .await;
+        }
+
+        metrics::HYBRID_BALANCE_CHECKS.inc();
+        let (db_balance, rpc_balance) = futures::try_join!(
+            self.db_client
+                .get_balance_before_block(account_id, block_header),
+            self.rpc_client
+                .get_balance_before_block(account_id, block_header),
+        )?;
+        if rpc_balance.non_staked == db_balance.non_staked
+            && rpc_balance.staked == db_balance.staked
+        {
+            return Ok(db_balance);
+        }
+
+        metrics::HYBRID_BALANCE_MISMATCHES.inc();
+        tracing::warn!(
+            target: crate::LOGGING_PREFIX,
+            "Balance of {} before block {} differs: DB {:?}, RPC {:?}. Using RPC",
+            account_id,
+            block_header.height,
+            db_balance,
+            rpc_balance,
+        );
+        let mismatch = BalanceMismatch {
+            block_height: BigDecimal::from(block_header.height),
+            block_timestamp: BigDecimal::from(block_header.timestamp),
+            account_id: account_id.to_string(),
+            db_non_staked: BigDecimal::from_str(&db_balance.non_staked.to_string())?,
+            db_staked: BigDecimal::from_str(&db_balance.staked.to_string())?,
+            rpc_non_staked: BigDecimal::from_str(&rpc_balance.non_staked.to_string())?,
+            rpc_staked: BigDecimal::from_str(&rpc_balance.staked.to_string())?,
+        };
+        self.mismatches_sender
+            .send(mismatch)
+            .await
+            .map_err(|_| anyhow::anyhow!("balance_mismatches writer is stopped"))?;
+
+        Ok(rpc_balance)
+    }
+}
+
+/// Writes the mismatches found by `HybridBalanceClient`, all the queued ones at once
+async fn store_mismatches(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    mut mismatches_receiver: tokio::sync::mpsc::Receiver<BalanceMismatch>,
+) {
+    while let Some(mismatch) = mismatches_receiver.recv().await {
+        let mut mismatches = vec![mismatch];
+        while let Ok(mismatch) = mismatches_receiver.try_recv() {
+            mismatches.push(mismatch);
+        }
+        if let Err(err) =
+            crate::models::chunked_insert(&pool, &mismatches, crate::RETRY_COUNT).await
+        {
+            tracing::error!(
+                target: crate::LOGGING_PREFIX,
+                "{} balance mismatches were not stored: {}",
+                mismatches.len(),
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO_HASH: &str = "11111111111111111111111111111111";
+
+    /// The DB which always has the same balance
+    struct FixedBalanceClient(crate::BalanceDetails);
+
+    #[async_trait]
+    impl BalanceClient for FixedBalanceClient {
+        async fn get_balance_before_block(
+            &self,
+            _account_id: &near_indexer_primitives::types::AccountId,
+            _block_header: &near_indexer_primitives::views::BlockHeaderView,
+        ) -> anyhow::Result<crate::BalanceDetails> {
+            Ok(self.0)
+        }
+    }
+
+    /// JSON RPC node where every account has 2 non-staked and 0 staked tokens
+    async fn mock_rpc(request: actix_web::web::Json<serde_json::Value>) -> actix_web::HttpResponse {
+        actix_web::HttpResponse::Ok().json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "amount": "2",
+                "locked": "0",
+                "code_hash": ZERO_HASH,
+                "storage_usage": 100,
+                "storage_paid_at": 0,
+                "block_height": 99,
+                "block_hash": ZERO_HASH
+            }
+        }))
+    }
+
+    fn block_header() -> near_indexer_primitives::views::BlockHeaderView {
+        serde_json::from_value(serde_json::json!({
+            "height": 100,
+            "prev_height": 99,
+            "epoch_id": ZERO_HASH,
+            "next_epoch_id": ZERO_HASH,
+            "hash": ZERO_HASH,
+            "prev_hash": ZERO_HASH,
+            "prev_state_root": ZERO_HASH,
+            "chunk_receipts_root": ZERO_HASH,
+            "chunk_headers_root": ZERO_HASH,
+            "chunk_tx_root": ZERO_HASH,
+            "outcome_root": ZERO_HASH,
+            "chunks_included": 1,
+            "challenges_root": ZERO_HASH,
+            "timestamp": 1_000_000_000u64,
+            "timestamp_nanosec": "1000000000",
+            "random_value": ZERO_HASH,
+            "validator_proposals": [],
+            "chunk_mask": [true],
+            "gas_price": "0",
+            "block_ordinal": 100,
+            "rent_paid": "0",
+            "validator_reward": "0",
+            "total_supply": "0",
+            "challenges_result": [],
+            "last_final_block": ZERO_HASH,
+            "last_ds_final_block": ZERO_HASH,
+            "next_bp_hash": ZERO_HASH,
+            "block_merkle_root": ZERO_HASH,
+            "epoch_sync_data_hash": null,
+            "approvals": [],
+            "signature": format!("ed25519:{}", "1".repeat(64)),
+            "latest_protocol_version": 58
+        }))
+        .expect("Test block header expected to be valid")
+    }
+
+    #[tokio::test]
+    async fn uses_rpc_balance_on_mismatch() -> anyhow::Result<()> {
+        let server = actix_web::HttpServer::new(|| {
+            actix_web::App::new().route("/", actix_web::web::post().to(mock_rpc))
+        })
+        .bind(("127.0.0.1", 0))?;
+        let rpc_url = format!("http://{}", server.addrs()[0]);
+        let server = server.run();
+        let server_handle = server.handle();
+        tokio::spawn(server);
+
+        let (mismatches_sender, mut mismatches_receiver) = tokio::sync::mpsc::channel(10);
+        let client = HybridBalanceClient::with_clients(
+            Box::new(FixedBalanceClient(crate::BalanceDetails {
+                non_staked: 1,
+                staked: 0,
+            })),
+            JsonRpcBalanceClient::new(near_jsonrpc_client::JsonRpcClient::connect(&rpc_url)),
+            0,
+            mismatches_sender,
+        );
+        let account_id = near_indexer_primitives::types::AccountId::from_str("alice.near")?;
+        let block_header = block_header();
+
+        // The first lookup of the account is checked
+        let first_balance = client
+            .get_balance_before_block(&account_id, &block_header)
+            .await;
+        // `check_every` is 0, so the next lookup is not checked
+        let second_balance = client
+            .get_balance_before_block(&account_id, &block_header)
+            .await;
+        server_handle.stop(false).await;
+
+        assert_eq!(first_balance?.non_staked, 2);
+        assert_eq!(second_balance?.non_staked, 1);
+        assert_eq!(
+            mismatches_receiver.try_recv()?,
+            BalanceMismatch {
+                block_height: BigDecimal::from(100),
+                block_timestamp: BigDecimal::from(1_000_000_000),
+                account_id: "alice.near".to_string(),
+                db_non_staked: BigDecimal::from(1),
+                db_staked: BigDecimal::from(0),
+                rpc_non_staked: BigDecimal::from(2),
+                rpc_staked: BigDecimal::from(0),
+            }
+        );
+        assert!(mismatches_receiver.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -30,6 +30,23 @@ pub(crate) async fn store_balance_changes(
     try_join_all(futures).await.map(|_| ())
 }
 
+/// Checks if the block produces at least one row in `near_balance_events`.
+/// Used by `audit` to find the expected block heights
+pub(crate) fn has_balance_changes(shards: &[near_indexer_primitives::IndexerShard]) -> bool {
+    shards.iter().any(|shard| {
+        shard.state_changes.iter().any(|state_change_with_cause| {
+            matches!(
+                state_change_with_cause.value,
+                near_indexer_primitives::views::StateChangeValueView::AccountUpdate { .. }
+                    | near_indexer_primitives::views::StateChangeValueView::AccountDeletion { .. }
+            ) && !matches!(
+                state_change_with_cause.cause,
+                StateChangeCauseView::Migration {}
+            )
+        })
+    })
+}
+
 #[derive(Debug, Default)]
 struct AccountChangesBalances {
     pub validators: Vec<crate::AccountWithBalance>,
@@ -91,7 +108,10 @@ async fn store_changes_for_chunk(
         change.event_index = BigDecimal::from_str(&(start_from_index + i as u128).to_string())?;
     }
 
-    crate::models::chunked_insert(pool, &changes, 10).await?;
+    // account_balances_latest should never be ahead or behind near_balance_events
+    let latest_balances =
+        crate::models::account_balances_latest::AccountBalanceLatest::from_events(&changes);
+    crate::models::insert_together_retry_or_panic(pool, &changes, &latest_balances, 10).await?;
 
     Ok(())
 }
@@ -1,8 +1,8 @@
 // // TODO cleanup imports in all the files in the end
 use futures::StreamExt;
-use indexer_opts::Parser;
 use near_lake_framework::near_indexer_primitives;
 
+mod audit;
 mod balance_client;
 mod cache;
 mod configs;
@@ -34,11 +34,16 @@ pub struct AccountWithBalance {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
-    let opts = indexer_opts::Opts::parse();
+    let opts = indexer_opts::Opts::parse_with_config(env!("CARGO_PKG_NAME"))?;
     configs::init_tracing(opts.debug)?;
 
     let pool = sqlx::PgPool::connect(&opts.database_url).await?;
-    let lake_config = opts.to_lake_config(&pool).await?;
+    if let Some(indexer_opts::Command::Audit(args)) = &opts.command {
+        return audit::run(&opts, args, &pool).await;
+    }
+    let lake_config = opts
+        .to_lake_config(&pool, env!("CARGO_PKG_VERSION"))
+        .await?;
     let (sender, stream) = near_lake_framework::streamer(lake_config);
     let end_block_height = opts.end_block_height.unwrap_or(u64::MAX);
 
@@ -51,13 +56,23 @@ async fn main() -> anyhow::Result<()> {
             Box::new(balance_client::PgBalanceClient::new(pool.clone()))
         }
         indexer_opts::BalanceMode::RPC => {
-            let rpc_url = opts
-                .rpc_url
-                .as_ref()
-                .expect("RPC_URL is required to run indexer-balances");
+            let rpc_url = opts.rpc_url.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("RPC_URL is required to run indexer-balances in rpc mode")
+            })?;
             let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(rpc_url);
             Box::new(balance_client::JsonRpcBalanceClient::new(json_rpc_client))
         }
+        indexer_opts::BalanceMode::Hybrid => {
+            let rpc_url = opts.rpc_url.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("RPC_URL is required to run indexer-balances in hybrid mode")
+            })?;
+            let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(rpc_url);
+            Box::new(balance_client::HybridBalanceClient::new(
+                pool.clone(),
+                json_rpc_client,
+                opts.hybrid_check_every,
+            ))
+        }
     };
 
     let mut handlers = tokio_stream::wrappers::ReceiverStream::new(stream)
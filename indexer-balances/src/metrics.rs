@@ -40,6 +40,16 @@ lazy_static! {
         "total cache misses",
     )
     .unwrap();
+    pub(crate) static ref HYBRID_BALANCE_CHECKS: IntCounter = try_create_int_counter(
+        "indexer_balances_hybrid_balance_checks",
+        "Total number of DB balances verified against JSON RPC in hybrid balance mode",
+    )
+    .unwrap();
+    pub(crate) static ref HYBRID_BALANCE_MISMATCHES: IntCounter = try_create_int_counter(
+        "indexer_balances_hybrid_balance_mismatches",
+        "Total number of DB balances which differ from JSON RPC in hybrid balance mode",
+    )
+    .unwrap();
     pub(crate) static ref CACHE_SIZE: IntGauge = try_create_int_gauge(
         "indexer_balances_cache_size",
         "total cache size",
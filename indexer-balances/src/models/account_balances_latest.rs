@@ -0,0 +1,82 @@
+use bigdecimal::BigDecimal;
+use sqlx::Arguments;
+
+use crate::models::balance_changes::NearBalanceEvent;
+use crate::models::FieldCount;
+
+/// The last row of `near_balance_events` for each account.
+/// Updated together with `near_balance_events`, used to avoid the lookups in the large table
+#[derive(Debug, sqlx::FromRow, FieldCount, PartialEq)]
+pub struct AccountBalanceLatest {
+    pub account_id: String,
+    pub non_staked: BigDecimal,
+    pub staked: BigDecimal,
+    pub block_height: BigDecimal,
+    pub event_index: BigDecimal,
+}
+
+impl AccountBalanceLatest {
+    pub fn from_event(event: &NearBalanceEvent) -> Self {
+        Self {
+            account_id: event.affected_account_id.clone(),
+            non_staked: event.absolute_nonstaked_amount.clone(),
+            staked: event.absolute_staked_amount.clone(),
+            block_height: event.block_height.clone(),
+            event_index: event.event_index.clone(),
+        }
+    }
+
+    /// Takes the last event of each affected account, the events should be ordered by `event_index`
+    pub fn from_events(events: &[NearBalanceEvent]) -> Vec<Self> {
+        let mut latest: std::collections::HashMap<&str, &NearBalanceEvent> =
+            std::collections::HashMap::new();
+        for event in events {
+            latest.insert(&event.affected_account_id, event);
+        }
+        let mut balances: Vec<Self> = latest.into_values().map(Self::from_event).collect();
+        // The shards upsert concurrently, the same order of the rows prevents the deadlocks
+        balances.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        balances
+    }
+}
+
+impl crate::models::SqlxMethods for AccountBalanceLatest {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.account_id);
+        args.add(&self.non_staked);
+        args.add(&self.staked);
+        args.add(&self.block_height);
+        args.add(&self.event_index);
+    }
+
+    // The shards of the block are stored concurrently, and the blocks could be reindexed,
+    // so the row is replaced only by the later event
+    fn insert_query(count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO account_balances_latest VALUES ".to_owned()
+            + &crate::models::create_placeholders(count, AccountBalanceLatest::field_count())?
+            + " ON CONFLICT (account_id) DO UPDATE
+                SET non_staked = EXCLUDED.non_staked,
+                    staked = EXCLUDED.staked,
+                    block_height = EXCLUDED.block_height,
+                    event_index = EXCLUDED.event_index
+                WHERE account_balances_latest.event_index < EXCLUDED.event_index")
+    }
+
+    fn name() -> String {
+        "account_balances_latest".to_string()
+    }
+}
+
+impl crate::models::SelectPrevBalance for AccountBalanceLatest {
+    // The caller should check `block_height`: the row could already contain the given block or the later ones
+    fn select_prev_balance_query(_block_height: u64, account_id: &str) -> String {
+        format!(
+            "
+                 SELECT *
+                 FROM account_balances_latest
+                 WHERE account_id = '{}';
+             ",
+            account_id
+        )
+    }
+}
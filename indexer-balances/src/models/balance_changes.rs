@@ -45,6 +45,12 @@ impl crate::models::SqlxMethods for NearBalanceEvent {
             + " ON CONFLICT DO NOTHING")
     }
 
+    fn name() -> String {
+        "near_balance_events".to_string()
+    }
+}
+
+impl crate::models::SelectPrevBalance for NearBalanceEvent {
     fn select_prev_balance_query(block_height: u64, account_id: &str) -> String {
         format!(
             "
@@ -58,8 +64,4 @@ impl crate::models::SqlxMethods for NearBalanceEvent {
             block_height, account_id
         )
     }
-
-    fn name() -> String {
-        "near_balance_events".to_string()
-    }
 }
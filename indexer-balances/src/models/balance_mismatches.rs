@@ -0,0 +1,38 @@
+use bigdecimal::BigDecimal;
+use sqlx::Arguments;
+
+use crate::models::FieldCount;
+
+/// The balance from the DB which differs from JSON RPC, found by `balance-mode hybrid`
+#[derive(Debug, sqlx::FromRow, FieldCount, PartialEq)]
+pub struct BalanceMismatch {
+    pub block_height: BigDecimal,
+    pub block_timestamp: BigDecimal,
+    pub account_id: String,
+    pub db_non_staked: BigDecimal,
+    pub db_staked: BigDecimal,
+    pub rpc_non_staked: BigDecimal,
+    pub rpc_staked: BigDecimal,
+}
+
+impl crate::models::SqlxMethods for BalanceMismatch {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.block_height);
+        args.add(&self.block_timestamp);
+        args.add(&self.account_id);
+        args.add(&self.db_non_staked);
+        args.add(&self.db_staked);
+        args.add(&self.rpc_non_staked);
+        args.add(&self.rpc_staked);
+    }
+
+    fn insert_query(count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO balance_mismatches VALUES ".to_owned()
+            + &crate::models::create_placeholders(count, BalanceMismatch::field_count())?
+            + " ON CONFLICT DO NOTHING")
+    }
+
+    fn name() -> String {
+        "balance_mismatches".to_string()
+    }
+}
@@ -5,8 +5,9 @@ use near_lake_framework::near_indexer_primitives::views::ExecutionStatusView;
 
 pub(crate) use indexer_balances::FieldCount;
 
-use self::balance_changes::NearBalanceEvent;
+pub(crate) mod account_balances_latest;
 pub(crate) mod balance_changes;
+pub(crate) mod balance_mismatches;
 
 pub trait FieldCount {
     /// Get the number of fields on a struct.
@@ -18,11 +19,14 @@ pub trait SqlxMethods {
 
     fn insert_query(count: usize) -> anyhow::Result<String>;
 
-    fn select_prev_balance_query(block_height: u64, account_id: &str) -> String;
-
     fn name() -> String;
 }
 
+/// The models which keep the balances and could give the previous balance of the account
+pub trait SelectPrevBalance {
+    fn select_prev_balance_query(block_height: u64, account_id: &str) -> String;
+}
+
 pub async fn chunked_insert<T: SqlxMethods + std::fmt::Debug>(
     pool: &sqlx::Pool<sqlx::Postgres>,
     items: &[T],
@@ -34,11 +38,14 @@ pub async fn chunked_insert<T: SqlxMethods + std::fmt::Debug>(
     try_join_all(futures).await.map(|_| ())
 }
 
-pub(crate) async fn select_one_retry_or_panic(
+pub(crate) async fn select_one_retry_or_panic<T>(
     pool: &sqlx::Pool<sqlx::Postgres>,
     query: &str,
     retry_count: usize,
-) -> anyhow::Result<Option<NearBalanceEvent>> {
+) -> anyhow::Result<Option<T>>
+where
+    T: for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+{
     let mut interval = crate::INTERVAL;
     let mut retry_attempt = 0usize;
 
@@ -51,10 +58,7 @@ pub(crate) async fn select_one_retry_or_panic(
         }
         retry_attempt += 1;
 
-        match sqlx::query_as::<_, NearBalanceEvent>(query)
-            .fetch_optional(pool)
-            .await
-        {
+        match sqlx::query_as::<_, T>(query).fetch_optional(pool).await {
             Ok(res) => return Ok(res),
             Err(async_error) => {
                 tracing::info!(
@@ -73,6 +77,80 @@ pub(crate) async fn select_one_retry_or_panic(
     }
 }
 
+/// Stores the items of both types in one DB transaction, so the readers never see only part of them
+pub async fn insert_together_retry_or_panic<T, U>(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    first: &[T],
+    second: &[U],
+    retry_count: usize,
+) -> anyhow::Result<()>
+where
+    T: SqlxMethods + std::fmt::Debug,
+    U: SqlxMethods + std::fmt::Debug,
+{
+    let mut interval = crate::INTERVAL;
+    let mut retry_attempt = 0usize;
+
+    loop {
+        if retry_attempt == retry_count {
+            return Err(anyhow::anyhow!(
+                "Failed to perform query to database after {} attempts. Stop trying.",
+                retry_count
+            ));
+        }
+        retry_attempt += 1;
+
+        match insert_in_transaction(pool, first, second).await {
+            Ok(_) => break,
+            Err(async_error) => {
+                tracing::error!(
+                         target: crate::LOGGING_PREFIX,
+                         "Error occurred during {}:\n{} and {} were not stored. \n{:#?} \n{:#?} \n Retrying in {} milliseconds...",
+                         async_error,
+                         &T::name(),
+                         &U::name(),
+                         &first,
+                         &second,
+                         interval.as_millis(),
+                     );
+                tokio::time::sleep(interval).await;
+                if interval < crate::MAX_DELAY_TIME {
+                    interval *= 2;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn insert_in_transaction<T: SqlxMethods, U: SqlxMethods>(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    first: &[T],
+    second: &[U],
+) -> anyhow::Result<()> {
+    let mut transaction = pool.begin().await?;
+    for items in first.chunks(crate::db_adapters::CHUNK_SIZE_FOR_BATCH_INSERT) {
+        let mut args = sqlx::postgres::PgArguments::default();
+        for item in items {
+            item.add_to_args(&mut args);
+        }
+        sqlx::query_with(&T::insert_query(items.len())?, args)
+            .execute(&mut transaction)
+            .await?;
+    }
+    for items in second.chunks(crate::db_adapters::CHUNK_SIZE_FOR_BATCH_INSERT) {
+        let mut args = sqlx::postgres::PgArguments::default();
+        for item in items {
+            item.add_to_args(&mut args);
+        }
+        sqlx::query_with(&U::insert_query(items.len())?, args)
+            .execute(&mut transaction)
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
 async fn insert_retry_or_panic<T: SqlxMethods + std::fmt::Debug>(
     pool: &sqlx::Pool<sqlx::Postgres>,
     items: &[T],
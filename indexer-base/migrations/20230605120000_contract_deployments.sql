@@ -0,0 +1,23 @@
+-- receipt_id is NULL for the code updates which are not caused by receipts (e.g. migrations)
+-- had_code_before is based on the deployments indexed before, it's not reliable for the blocks
+-- before the start of the indexing
+-- The code of the account at block H:
+--     SELECT code_hash FROM contract_deployments WHERE account_id = $1 AND block_height <= H
+--     ORDER BY block_height DESC, index_in_chunk DESC LIMIT 1
+CREATE TABLE contract_deployments
+(
+    account_id           text           NOT NULL,
+    code_hash            text           NOT NULL,
+    code_size            bigint         NOT NULL,
+    receipt_id           text,
+    block_height         numeric(20, 0) NOT NULL,
+    block_timestamp      numeric(20, 0) NOT NULL,
+    had_code_before      boolean        NOT NULL,
+    chunk_index_in_block integer        NOT NULL,
+    index_in_chunk       integer        NOT NULL,
+    PRIMARY KEY (block_timestamp, chunk_index_in_block, index_in_chunk)
+);
+
+CREATE INDEX CONCURRENTLY contract_deployments_account_height_idx ON contract_deployments (account_id, block_height);
+CREATE INDEX CONCURRENTLY contract_deployments_code_hash_idx ON contract_deployments (code_hash);
+CREATE INDEX CONCURRENTLY contract_deployments_receipt_id_idx ON contract_deployments (receipt_id);
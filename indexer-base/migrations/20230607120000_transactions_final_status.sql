@@ -0,0 +1,16 @@
+-- final_status options:
+--      {
+--         'SUCCESS',
+--         'FAILURE'
+--      }
+-- The status is taken from the transaction outcome, following its SuccessReceiptId links,
+-- like the final execution status of JSON RPC: the failed Receipt handled by the callback does not fail the transaction.
+-- The columns are filled when the last Receipt of the transaction is executed.
+-- They stay NULL for the transactions started before the first indexed block.
+ALTER TABLE transactions
+    ADD COLUMN final_status              text,
+    ADD COLUMN total_gas_burnt           numeric(20, 0),
+    ADD COLUMN total_tokens_burnt        numeric(38, 0),
+    ADD COLUMN completed_at_block_height numeric(20, 0);
+
+CREATE INDEX CONCURRENTLY transactions_final_status_idx ON transactions (final_status);
@@ -0,0 +1,30 @@
+-- Filled only for the contracts from `--watched-contracts`
+-- change_kind options:
+--      {
+--         'DATA_UPDATE',
+--         'DATA_DELETION',
+--         'ACCESS_KEY_UPDATE',
+--         'ACCESS_KEY_DELETION'
+--      }
+-- For the data changes, key and value are the raw contract storage bytes, value is NULL for the deletion.
+-- For the access key changes, key is the public key and value is JSON of the access key.
+-- The contract code changes are stored in contract_deployments.
+CREATE TABLE contract_state_changes
+(
+    account_id                 text           NOT NULL,
+    change_kind                text           NOT NULL,
+    key                        bytea          NOT NULL,
+    value                      bytea,
+    caused_by_transaction_hash text,
+    caused_by_receipt_id       text,
+    update_reason              text           NOT NULL,
+    block_height               numeric(20, 0) NOT NULL,
+    block_timestamp            numeric(20, 0) NOT NULL,
+    chunk_index_in_block       integer        NOT NULL,
+    index_in_chunk             integer        NOT NULL,
+    PRIMARY KEY (block_timestamp, chunk_index_in_block, index_in_chunk)
+);
+
+CREATE INDEX CONCURRENTLY contract_state_changes_account_key_idx ON contract_state_changes (account_id, key, block_height);
+CREATE INDEX CONCURRENTLY contract_state_changes_receipt_id_idx ON contract_state_changes (caused_by_receipt_id);
+CREATE INDEX CONCURRENTLY contract_state_changes_tx_hash_idx ON contract_state_changes (caused_by_transaction_hash);
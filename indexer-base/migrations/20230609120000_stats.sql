@@ -0,0 +1,40 @@
+-- receipts_count is the number of the Receipts executed in the block
+-- gas_used_by_shard, gas_limit_by_shard are indexed by shard_id
+CREATE TABLE block_stats
+(
+    block_height       numeric(20, 0) NOT NULL,
+    block_hash         text           NOT NULL,
+    block_timestamp    numeric(20, 0) NOT NULL,
+    transactions_count integer        NOT NULL,
+    receipts_count     integer        NOT NULL,
+    gas_used           numeric(20, 0) NOT NULL,
+    gas_limit          numeric(20, 0) NOT NULL,
+    gas_used_by_shard  bigint[]       NOT NULL,
+    gas_limit_by_shard bigint[]       NOT NULL,
+    tokens_burnt       numeric(38, 0) NOT NULL,
+    unique_signers     integer        NOT NULL,
+    PRIMARY KEY (block_height)
+);
+CREATE INDEX CONCURRENTLY block_stats_timestamp_idx ON block_stats (block_timestamp);
+
+-- Updated incrementally with each new row in block_stats, the days are in UTC
+CREATE TABLE daily_stats
+(
+    date               date           NOT NULL,
+    blocks_count       integer        NOT NULL,
+    transactions_count bigint         NOT NULL,
+    receipts_count     bigint         NOT NULL,
+    gas_used           numeric(30, 0) NOT NULL,
+    gas_limit          numeric(30, 0) NOT NULL,
+    tokens_burnt       numeric(45, 0) NOT NULL,
+    unique_signers     integer        NOT NULL,
+    PRIMARY KEY (date)
+);
+
+-- Helper table to count unique signers per day incrementally
+CREATE TABLE daily_signers
+(
+    date       date NOT NULL,
+    account_id text NOT NULL,
+    PRIMARY KEY (date, account_id)
+);
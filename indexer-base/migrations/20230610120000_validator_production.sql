@@ -0,0 +1,29 @@
+-- The blocks already added to validator_production, protects the aggregates from counting the block twice
+CREATE TABLE validator_production_blocks
+(
+    block_height numeric(20, 0) NOT NULL,
+    epoch_id     text           NOT NULL,
+    PRIMARY KEY (block_height)
+);
+
+-- gas_used is the gas used by the chunks produced by the validator
+CREATE TABLE validator_production
+(
+    epoch_id        text           NOT NULL,
+    account_id      text           NOT NULL,
+    blocks_produced integer        NOT NULL,
+    chunks_produced integer        NOT NULL,
+    gas_used        numeric(30, 0) NOT NULL,
+    PRIMARY KEY (epoch_id, account_id)
+);
+CREATE INDEX CONCURRENTLY validator_production_account_id_idx ON validator_production (account_id);
+
+-- The block does not contain the producer of the missing chunk,
+-- use `validators` RPC method to match the missed chunks with the validators
+CREATE TABLE epoch_missed_chunks
+(
+    epoch_id      text    NOT NULL,
+    shard_id      integer NOT NULL,
+    chunks_missed integer NOT NULL,
+    PRIMARY KEY (epoch_id, shard_id)
+);
@@ -0,0 +1,29 @@
+-- The normalized FUNCTION_CALL actions, (block_timestamp, chunk_index_in_block, index_in_chunk)
+-- matches the row in action_receipts__actions
+-- args_size is the size of the decoded arguments in bytes
+-- args_json is filled only if the arguments are JSON not larger than `function-call-args-max-size`
+-- args_base64 is filled if the arguments are not JSON, or it's the truncated arguments if args_truncated
+CREATE TABLE function_calls
+(
+    block_timestamp        numeric(20, 0) NOT NULL,
+    receipt_id             text           NOT NULL,
+    predecessor_account_id text           NOT NULL,
+    receiver_account_id    text           NOT NULL,
+    method_name            text           NOT NULL,
+    deposit                numeric(45, 0) NOT NULL,
+    gas                    numeric(20, 0) NOT NULL,
+    args_size              integer        NOT NULL,
+    args_json              jsonb,
+    args_base64            text,
+    args_truncated         boolean        NOT NULL,
+    chunk_index_in_block   integer        NOT NULL,
+    index_in_chunk         integer        NOT NULL,
+    PRIMARY KEY (block_timestamp, chunk_index_in_block, index_in_chunk)
+);
+
+ALTER TABLE function_calls
+    ADD CONSTRAINT function_calls_receipt_id_fk FOREIGN KEY (receipt_id) REFERENCES action_receipts (receipt_id);
+
+CREATE INDEX CONCURRENTLY function_calls_receiver_method_timestamp_idx ON function_calls (receiver_account_id, method_name, block_timestamp);
+CREATE INDEX CONCURRENTLY function_calls_predecessor_timestamp_idx ON function_calls (predecessor_account_id, block_timestamp);
+CREATE INDEX CONCURRENTLY function_calls_receipt_id_idx ON function_calls (receipt_id);
@@ -0,0 +1,30 @@
+-- The blocks already added to supply_series, protects the aggregates from counting the block twice
+CREATE TABLE supply_series_blocks
+(
+    block_height numeric(20, 0) NOT NULL,
+    PRIMARY KEY (block_height)
+);
+
+-- period_kind is EPOCH (period_id is epoch_id) or DAY (period_id is UTC date, YYYY-MM-DD)
+-- start_total_supply is the supply before the first block of the period, end_total_supply is after the last one
+-- tokens_burnt is summed from the execution outcomes, chunks_balance_burnt is summed from the chunk headers
+-- minted = supply change + chunks_balance_burnt
+-- is_consistent checks that supply change = minted - tokens_burnt. The chunk header reports the burnt tokens of
+-- the previous chunk of the shard, so the border blocks of the period could make it false by a small amount
+CREATE TABLE supply_series
+(
+    period_kind          text           NOT NULL,
+    period_id            text           NOT NULL,
+    start_block_height   numeric(20, 0) NOT NULL,
+    end_block_height     numeric(20, 0) NOT NULL,
+    start_total_supply   numeric(45, 0) NOT NULL,
+    end_total_supply     numeric(45, 0) NOT NULL,
+    tokens_burnt         numeric(45, 0) NOT NULL,
+    chunks_balance_burnt numeric(45, 0) NOT NULL,
+    minted               numeric(45, 0) NOT NULL,
+    min_gas_price        numeric(45, 0) NOT NULL,
+    max_gas_price        numeric(45, 0) NOT NULL,
+    is_consistent        boolean GENERATED ALWAYS AS (end_total_supply - start_total_supply = minted - tokens_burnt) STORED,
+    PRIMARY KEY (period_kind, period_id)
+);
+CREATE INDEX CONCURRENTLY supply_series_start_block_height_idx ON supply_series (period_kind, start_block_height);
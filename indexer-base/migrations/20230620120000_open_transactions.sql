@@ -0,0 +1,28 @@
+-- The state of the transactions which still have the Receipts to be executed.
+-- indexer-base keeps it in memory and persists it with each block, so it survives restarts.
+CREATE TABLE open_transactions
+(
+    transaction_hash        text           NOT NULL PRIMARY KEY,
+    outstanding_receipts    integer        NOT NULL,
+    gas_burnt               numeric(20, 0) NOT NULL,
+    tokens_burnt            numeric(38, 0) NOT NULL,
+    -- The Receipt which outcome defines the status of the transaction (the end of SuccessReceiptId chain)
+    status_receipt_id       text,
+    final_status            text,
+    started_at_block_height numeric(20, 0) NOT NULL
+);
+
+CREATE TABLE open_transactions_receipts
+(
+    receipt_id       text NOT NULL PRIMARY KEY,
+    transaction_hash text NOT NULL
+);
+
+CREATE INDEX open_transactions_receipts_transaction_hash_idx ON open_transactions_receipts (transaction_hash);
+
+-- The last block applied to the state, the blocks up to it are skipped after the restart
+CREATE TABLE open_transactions_progress
+(
+    id                boolean        NOT NULL PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_block_height numeric(20, 0) NOT NULL
+);
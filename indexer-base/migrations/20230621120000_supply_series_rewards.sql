@@ -0,0 +1,9 @@
+-- minted is the sum of the protocol rewards given at the first block of the epoch: the growth of the
+-- nonstaked + staked balance of the accounts with VALIDATOR_ACCOUNTS_UPDATE changes since their previous
+-- account_changes row. It is NULL if any rewarded account had no stored changes before the block
+-- (the first indexed epoch or the account is filtered out), is_consistent is NULL then.
+-- The old values were derived from the supply change itself, so they are cleared
+ALTER TABLE supply_series
+    ALTER COLUMN minted DROP NOT NULL;
+UPDATE supply_series
+SET minted = NULL;
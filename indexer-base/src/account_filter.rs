@@ -0,0 +1,99 @@
+/// Restricts the stored transactions, receipts, execution outcomes and account changes
+/// to the ones involving the given accounts.
+/// Each pattern is either the exact account (`token.sweat`) or the suffix (`*.sweat`).
+/// The deny-list wins over the allow-list; the empty allow-list allows all the accounts.
+#[derive(Debug, Default)]
+pub struct AccountFilter {
+    allow: Vec<AccountPattern>,
+    deny: Vec<AccountPattern>,
+}
+
+#[derive(Debug)]
+enum AccountPattern {
+    Exact(String),
+    // Stored with the leading dot, `*.sweat` becomes `.sweat`
+    Suffix(String),
+}
+
+impl AccountPattern {
+    fn new(pattern: &str) -> Self {
+        match pattern.trim().strip_prefix('*') {
+            Some(suffix) => Self::Suffix(suffix.to_string()),
+            None => Self::Exact(pattern.trim().to_string()),
+        }
+    }
+
+    fn matches(&self, account_id: &str) -> bool {
+        match self {
+            Self::Exact(exact) => account_id == exact,
+            Self::Suffix(suffix) => account_id.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+impl AccountFilter {
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        Self {
+            allow: allow
+                .iter()
+                .map(|pattern| AccountPattern::new(pattern))
+                .collect(),
+            deny: deny
+                .iter()
+                .map(|pattern| AccountPattern::new(pattern))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    pub fn is_allowed(&self, account_id: &str) -> bool {
+        !self.deny.iter().any(|pattern| pattern.matches(account_id))
+            && (self.allow.is_empty()
+                || self.allow.iter().any(|pattern| pattern.matches(account_id)))
+    }
+
+    /// The item (transaction, receipt, etc.) is stored if at least one of its accounts is allowed
+    pub fn is_any_allowed<'a>(&self, account_ids: impl IntoIterator<Item = &'a str>) -> bool {
+        self.is_empty()
+            || account_ids
+                .into_iter()
+                .any(|account_id| self.is_allowed(account_id))
+    }
+}
+
+/// The foreign keys which are violated by the filtered in rows, since their parents could be filtered out
+const FILTER_INCOMPATIBLE_FOREIGN_KEYS: [(&str, &str); 4] = [
+    ("action_receipts", "action_receipts_transaction_hash_fk"),
+    ("data_receipts", "data_receipts_tx_hash_fk"),
+    ("account_changes", "account_changes_receipt_id_fk"),
+    ("account_changes", "account_changes_tx_hash_fk"),
+];
+
+/// Fails if the database still has the foreign keys which make the filtered inserts fail
+pub(crate) async fn check_foreign_keys(pool: &sqlx::Pool<sqlx::Postgres>) -> anyhow::Result<()> {
+    let names: Vec<String> = FILTER_INCOMPATIBLE_FOREIGN_KEYS
+        .iter()
+        .map(|(_, name)| name.to_string())
+        .collect();
+    let existing: Vec<String> = sqlx::query_scalar(
+        "SELECT conname::text FROM pg_constraint WHERE contype = 'f' AND conname = ANY($1)",
+    )
+    .bind(&names)
+    .fetch_all(pool)
+    .await?;
+    if existing.is_empty() {
+        return Ok(());
+    }
+    let statements: Vec<String> = FILTER_INCOMPATIBLE_FOREIGN_KEYS
+        .iter()
+        .filter(|(_, name)| existing.iter().any(|existing| existing == name))
+        .map(|(table, name)| format!("ALTER TABLE {} DROP CONSTRAINT {};", table, name))
+        .collect();
+    anyhow::bail!(
+        "The account filter requires dropping the foreign keys to the filtered out parents:\n{}",
+        statements.join("\n")
+    )
+}
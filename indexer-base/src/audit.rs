@@ -0,0 +1,17 @@
+/// Reports the holes in `blocks` table.
+/// `blocks` is the source of truth for the other tables of `indexer-base`,
+/// they are stored only after the block is stored.
+pub(crate) async fn run(
+    opts: &indexer_opts::Opts,
+    args: &indexer_opts::audit::AuditArgs,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<()> {
+    let (from_block_height, to_block_height) = opts.audit_range(args, pool).await?;
+    indexer_opts::audit::report_holes_in_blocks(
+        pool,
+        &opts.indexer_id,
+        from_block_height,
+        to_block_height,
+    )
+    .await
+}
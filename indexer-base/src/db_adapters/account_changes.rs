@@ -8,6 +8,7 @@ pub(crate) async fn store_account_changes(
     shards: &[near_indexer_primitives::IndexerShard],
     block_hash: &near_indexer_primitives::CryptoHash,
     block_timestamp: u64,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
     let futures = shards.iter().map(|shard| {
         store_account_changes_for_chunk(
@@ -16,6 +17,7 @@ pub(crate) async fn store_account_changes(
             block_hash,
             block_timestamp,
             shard.shard_id,
+            account_filter,
         )
     });
 
@@ -28,6 +30,7 @@ async fn store_account_changes_for_chunk(
     block_hash: &near_indexer_primitives::CryptoHash,
     block_timestamp: u64,
     shard_id: near_indexer_primitives::types::ShardId,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
     models::chunked_insert(
         pool,
@@ -48,6 +51,7 @@ async fn store_account_changes_for_chunk(
                 account_change.index_in_chunk = i as i32;
                 account_change
             })
+            .filter(|account_change| account_filter.is_allowed(&account_change.account_id))
             .collect::<Vec<models::AccountChange>>(),
     )
     .await?;
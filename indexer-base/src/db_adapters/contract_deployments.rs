@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use near_lake_framework::near_indexer_primitives;
+use sqlx::Row;
+
+use crate::models;
+
+/// Saves the contract code updates of the block to `contract_deployments`.
+/// `had_code_before` is based on the deployments indexed before, including the ones from the same block
+pub(crate) async fn store_contract_deployments(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    shards: &[near_indexer_primitives::IndexerShard],
+    block_header: &near_indexer_primitives::views::BlockHeaderView,
+    account_filter: &crate::account_filter::AccountFilter,
+) -> anyhow::Result<()> {
+    let mut deployments: Vec<models::ContractDeployment> = shards
+        .iter()
+        .flat_map(|shard| {
+            shard
+                .state_changes
+                .iter()
+                .filter_map(move |state_change| {
+                    models::ContractDeployment::from_state_change_with_cause(
+                        state_change,
+                        block_header,
+                        shard.shard_id as i32,
+                        // we fill it later because we can't enumerate before filtering finishes
+                        0,
+                    )
+                })
+                .enumerate()
+                .map(|(i, mut deployment)| {
+                    deployment.index_in_chunk = i as i32;
+                    deployment
+                })
+        })
+        .filter(|deployment| account_filter.is_allowed(&deployment.account_id))
+        .collect();
+    if deployments.is_empty() {
+        return Ok(());
+    }
+
+    let mut accounts_with_code =
+        find_accounts_with_previous_deployments(pool, &deployments, block_header.height).await?;
+    for deployment in deployments.iter_mut() {
+        deployment.had_code_before = !accounts_with_code.insert(deployment.account_id.clone());
+    }
+
+    models::chunked_insert(pool, &deployments).await
+}
+
+async fn find_accounts_with_previous_deployments(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    deployments: &[models::ContractDeployment],
+    block_height: u64,
+) -> anyhow::Result<HashSet<String>> {
+    let mut substitution_items: Vec<String> = deployments
+        .iter()
+        .map(|deployment| deployment.account_id.clone())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+    let query = "SELECT DISTINCT account_id FROM contract_deployments WHERE block_height < $1::numeric AND account_id IN "
+        .to_owned()
+        + &models::create_placeholder(&mut 2, substitution_items.len())?;
+    substitution_items.insert(0, block_height.to_string());
+
+    let res = models::select_retry_or_panic(pool, &query, &substitution_items).await?;
+    Ok(res.iter().map(|row| row.get(0)).collect())
+}
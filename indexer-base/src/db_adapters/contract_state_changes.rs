@@ -0,0 +1,42 @@
+use futures::future::try_join_all;
+use near_lake_framework::near_indexer_primitives;
+
+use crate::models;
+
+/// Saves the storage and access key changes of the watched contracts.
+/// Does nothing if no contracts are watched, the table is opt-in
+pub(crate) async fn store_contract_state_changes(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    shards: &[near_indexer_primitives::IndexerShard],
+    block_header: &near_indexer_primitives::views::BlockHeaderView,
+    watched_contracts: &crate::account_filter::AccountFilter,
+) -> anyhow::Result<()> {
+    if watched_contracts.is_empty() {
+        return Ok(());
+    }
+
+    let futures = shards.iter().map(|shard| {
+        let state_changes = shard
+            .state_changes
+            .iter()
+            .filter_map(|state_change| {
+                models::ContractStateChange::from_state_change_with_cause(
+                    state_change,
+                    block_header,
+                    shard.shard_id as i32,
+                    // we fill it later because we can't enumerate before filtering finishes
+                    0,
+                )
+            })
+            .enumerate()
+            .map(|(i, mut state_change)| {
+                state_change.index_in_chunk = i as i32;
+                state_change
+            })
+            .filter(|state_change| watched_contracts.is_allowed(&state_change.account_id))
+            .collect::<Vec<models::ContractStateChange>>();
+        async move { models::chunked_insert(pool, &state_changes).await }
+    });
+
+    try_join_all(futures).await.map(|_| ())
+}
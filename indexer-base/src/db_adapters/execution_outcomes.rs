@@ -1,4 +1,3 @@
-use cached::Cached;
 use futures::future::try_join_all;
 use near_lake_framework::near_indexer_primitives;
 
@@ -10,6 +9,7 @@ pub(crate) async fn store_execution_outcomes(
     block_hash: &near_indexer_primitives::CryptoHash,
     block_timestamp: u64,
     receipts_cache: crate::ReceiptsCache,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
     let futures = shards.iter().map(|shard| {
         store_execution_outcomes_for_chunk(
@@ -19,6 +19,7 @@ pub(crate) async fn store_execution_outcomes(
             block_hash,
             block_timestamp,
             receipts_cache.clone(),
+            account_filter,
         )
     });
 
@@ -33,12 +34,21 @@ pub async fn store_execution_outcomes_for_chunk(
     block_hash: &near_indexer_primitives::CryptoHash,
     block_timestamp: u64,
     receipts_cache: crate::ReceiptsCache,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
+    let is_allowed = |outcome: &near_indexer_primitives::IndexerExecutionOutcomeWithReceipt| {
+        account_filter.is_any_allowed([
+            outcome.receipt.predecessor_id.as_str(),
+            outcome.receipt.receiver_id.as_str(),
+        ])
+    };
+
     models::chunked_insert(
         pool,
         &execution_outcomes
             .iter()
             .enumerate()
+            .filter(|(_, outcome)| is_allowed(outcome))
             .map(|(index_in_chunk, outcome)| {
                 models::ExecutionOutcome::from_execution_outcome(
                     &outcome.execution_outcome,
@@ -52,14 +62,16 @@ pub async fn store_execution_outcomes_for_chunk(
     .await?;
 
     let mut outcome_receipt_models: Vec<models::ExecutionOutcomeReceipt> = vec![];
-    let mut receipts_cache_lock = receipts_cache.lock().await;
     for outcome in execution_outcomes {
         // Trying to take the parent Transaction hash for the Receipt from ReceiptsCache
         // remove it from cache once found as it is not expected to observe the Receipt for
         // second time
-        let parent_transaction_hash = receipts_cache_lock.cache_remove(
-            &crate::ReceiptOrDataId::ReceiptId(outcome.execution_outcome.id),
-        );
+        let parent_transaction_hash = receipts_cache
+            .remove(&crate::ReceiptOrDataId::ReceiptId(
+                outcome.execution_outcome.id,
+            ))
+            .await;
+        let mut receipts_cache_pairs = vec![];
 
         outcome_receipt_models.extend(outcome.execution_outcome.outcome.receipt_ids.iter().map(
             |receipt_id| {
@@ -67,10 +79,10 @@ pub async fn store_execution_outcomes_for_chunk(
                 // as key and `parent_transaction_hash` as value, so the Receipts from one of the next blocks
                 // could find their parents in cache
                 if let Some(transaction_hash) = &parent_transaction_hash {
-                    receipts_cache_lock.cache_set(
+                    receipts_cache_pairs.push((
                         crate::ReceiptOrDataId::ReceiptId(*receipt_id),
                         transaction_hash.clone(),
-                    );
+                    ));
                 }
 
                 models::ExecutionOutcomeReceipt {
@@ -84,8 +96,8 @@ pub async fn store_execution_outcomes_for_chunk(
                 }
             },
         ));
+        receipts_cache.set_many(receipts_cache_pairs).await;
     }
-    drop(receipts_cache_lock);
 
     outcome_receipt_models
         .iter_mut()
@@ -93,6 +105,18 @@ pub async fn store_execution_outcomes_for_chunk(
         .for_each(|(i, execution_outcomes_receipt)| {
             execution_outcomes_receipt.index_in_chunk = i as i32;
         });
+    // The cache above is updated for all the outcomes, so the filtered in Receipts could find
+    // their parents even if they were produced by the filtered out ones
+    if !account_filter.is_empty() {
+        let allowed_receipt_ids: std::collections::HashSet<String> = execution_outcomes
+            .iter()
+            .filter(|outcome| is_allowed(outcome))
+            .map(|outcome| outcome.execution_outcome.id.to_string())
+            .collect();
+        outcome_receipt_models.retain(|execution_outcomes_receipt| {
+            allowed_receipt_ids.contains(&execution_outcomes_receipt.executed_receipt_id)
+        });
+    }
 
     models::chunked_insert(pool, &outcome_receipt_models).await?;
 
@@ -1,9 +1,15 @@
 pub(crate) mod account_changes;
 pub(crate) mod blocks;
 pub(crate) mod chunks;
+pub(crate) mod contract_deployments;
+pub(crate) mod contract_state_changes;
 pub(crate) mod execution_outcomes;
 pub(crate) mod receipts;
+pub(crate) mod stats;
+pub(crate) mod supply;
+pub(crate) mod transaction_statuses;
 pub(crate) mod transactions;
+pub(crate) mod validator_production;
 
 pub(crate) const CHUNK_SIZE_FOR_BATCH_INSERT: usize = 100;
 pub(crate) const RETRY_COUNT: usize = 10;
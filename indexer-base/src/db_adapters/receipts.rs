@@ -2,7 +2,6 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
-use cached::Cached;
 use futures::future::try_join_all;
 use futures::try_join;
 use itertools::{Either, Itertools};
@@ -16,24 +15,16 @@ use crate::models;
 /// Saves receipts to database
 pub(crate) async fn store_receipts(
     pool: &sqlx::Pool<sqlx::Postgres>,
-    strict_mode: bool,
     shards: &[near_indexer_primitives::IndexerShard],
     block_header: &near_indexer_primitives::views::BlockHeaderView,
-    receipts_cache: crate::ReceiptsCache,
+    context: &crate::IndexingContext,
 ) -> anyhow::Result<()> {
     let futures = shards
         .iter()
         .filter_map(|shard| shard.chunk.as_ref())
         .filter(|chunk| !chunk.receipts.is_empty())
         .map(|chunk| {
-            store_chunk_receipts(
-                pool,
-                strict_mode,
-                &chunk.receipts,
-                block_header,
-                &chunk.header,
-                std::sync::Arc::clone(&receipts_cache),
-            )
+            store_chunk_receipts(pool, &chunk.receipts, block_header, &chunk.header, context)
         });
 
     try_join_all(futures).await.map(|_| ())
@@ -41,12 +32,14 @@ pub(crate) async fn store_receipts(
 
 async fn store_chunk_receipts(
     pool: &sqlx::Pool<sqlx::Postgres>,
-    strict_mode: bool,
     receipts: &[near_indexer_primitives::views::ReceiptView],
     block_header: &near_indexer_primitives::views::BlockHeaderView,
     chunk_header: &near_indexer_primitives::views::ChunkHeaderView,
-    receipts_cache: crate::ReceiptsCache,
+    context: &crate::IndexingContext,
 ) -> anyhow::Result<()> {
+    let strict_mode = context.strict_mode;
+    let account_filter = &context.account_filter;
+    let receipts_cache = context.receipts_cache.clone();
     let tx_hashes_for_receipts: HashMap<
         crate::ReceiptOrDataId,
         crate::ParentTransactionHashString,
@@ -56,13 +49,14 @@ async fn store_chunk_receipts(
         receipts.to_vec(),
         block_header.height,
         receipts_cache.clone(),
+        account_filter,
     )
     .await?;
 
     // At the moment we can observe output data in the Receipt it's impossible to know
     // the Receipt Id of that Data Receipt. That's why we insert the pair DataId<>ParentTransactionHash
     // to ReceiptsCache
-    let mut receipts_cache_lock = receipts_cache.lock().await;
+    let mut receipts_cache_pairs = vec![];
     for receipt in receipts {
         if let near_indexer_primitives::views::ReceiptEnumView::Action {
             output_data_receivers,
@@ -74,17 +68,16 @@ async fn store_chunk_receipts(
                     .get(&crate::ReceiptOrDataId::ReceiptId(receipt.receipt_id))
                 {
                     for data_receiver in output_data_receivers {
-                        receipts_cache_lock.cache_set(
+                        receipts_cache_pairs.push((
                             crate::ReceiptOrDataId::DataId(data_receiver.data_id),
                             transaction_hash.clone(),
-                        );
+                        ));
                     }
                 }
             }
         }
     }
-    // releasing the lock
-    drop(receipts_cache_lock);
+    receipts_cache.set_many(receipts_cache_pairs).await;
 
     // enumeration goes through all the receipts
     let enumerated_receipts_with_parent_tx: Vec<(
@@ -106,8 +99,13 @@ async fn store_chunk_receipts(
                     .map(|tx| (index, tx, receipt))
             }
         })
+        .filter(|(_, _, receipt)| is_receipt_allowed(receipt, account_filter))
         .collect();
-    if strict_mode && receipts.len() != enumerated_receipts_with_parent_tx.len() {
+    let allowed_receipts_count = receipts
+        .iter()
+        .filter(|receipt| is_receipt_allowed(receipt, account_filter))
+        .count();
+    if strict_mode && allowed_receipts_count != enumerated_receipts_with_parent_tx.len() {
         // todo maybe it's better to collect blocks for rerun here
         return Err(anyhow::anyhow!(
             "Some tx hashes were not found at block {}",
@@ -126,8 +124,13 @@ async fn store_chunk_receipts(
         },
     );
 
-    let process_receipt_actions_future =
-        store_receipt_actions(pool, action_receipts, block_header, chunk_header);
+    let process_receipt_actions_future = store_receipt_actions(
+        pool,
+        action_receipts,
+        block_header,
+        chunk_header,
+        context.args_limit,
+    );
 
     let process_receipt_data_future =
         store_data_receipts(pool, data_receipts, block_header, chunk_header);
@@ -143,40 +146,33 @@ async fn find_tx_hashes_for_receipts(
     mut receipts: Vec<near_indexer_primitives::views::ReceiptView>,
     block_height: u64,
     receipts_cache: crate::ReceiptsCache,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<HashMap<crate::ReceiptOrDataId, crate::ParentTransactionHashString>> {
     let mut tx_hashes_for_receipts: HashMap<
         crate::ReceiptOrDataId,
         crate::ParentTransactionHashString,
     > = HashMap::new();
 
-    let mut receipts_cache_lock = receipts_cache.lock().await;
     // add receipt-transaction pairs from the cache to the response
-    tx_hashes_for_receipts.extend(receipts.iter().filter_map(|receipt| {
-        match receipt.receipt {
-            near_indexer_primitives::views::ReceiptEnumView::Action { .. } => receipts_cache_lock
-                .cache_get(&crate::ReceiptOrDataId::ReceiptId(receipt.receipt_id))
-                .map(|parent_transaction_hash| {
-                    (
-                        crate::ReceiptOrDataId::ReceiptId(receipt.receipt_id),
-                        parent_transaction_hash.clone(),
-                    )
-                }),
+    for receipt in receipts.iter() {
+        let (key, parent_transaction_hash) = match receipt.receipt {
+            near_indexer_primitives::views::ReceiptEnumView::Action { .. } => {
+                let key = crate::ReceiptOrDataId::ReceiptId(receipt.receipt_id);
+                let parent_transaction_hash = receipts_cache.get(&key).await;
+                (key, parent_transaction_hash)
+            }
             near_indexer_primitives::views::ReceiptEnumView::Data { data_id, .. } => {
                 // Pair DataId:ParentTransactionHash won't be used after this moment
                 // We want to clean it up to prevent our cache from growing
-                receipts_cache_lock
-                    .cache_remove(&crate::ReceiptOrDataId::DataId(data_id))
-                    .map(|parent_transaction_hash| {
-                        (
-                            crate::ReceiptOrDataId::DataId(data_id),
-                            parent_transaction_hash,
-                        )
-                    })
+                let key = crate::ReceiptOrDataId::DataId(data_id);
+                let parent_transaction_hash = receipts_cache.remove(&key).await;
+                (key, parent_transaction_hash)
             }
+        };
+        if let Some(parent_transaction_hash) = parent_transaction_hash {
+            tx_hashes_for_receipts.insert(key, parent_transaction_hash);
         }
-    }));
-    // releasing the lock
-    drop(receipts_cache_lock);
+    }
 
     // discard the Receipts already in cache from the attempts to search
     receipts.retain(|r| match r.receipt {
@@ -247,6 +243,8 @@ async fn find_tx_hashes_for_receipts(
         });
     }
 
+    // The filtered out Receipts are not stored, it's fine if we can't find their parents
+    receipts.retain(|r| is_receipt_allowed(r, account_filter));
     if !receipts.is_empty() {
         eprintln!(
             "The block {} has {} receipt(s) we still need to put to the DB later: {:?}",
@@ -270,6 +268,122 @@ async fn find_tx_hashes_for_receipts(
     Ok(tx_hashes_for_receipts)
 }
 
+fn is_receipt_allowed(
+    receipt: &near_indexer_primitives::views::ReceiptView,
+    account_filter: &crate::account_filter::AccountFilter,
+) -> bool {
+    account_filter.is_any_allowed([
+        receipt.predecessor_id.as_str(),
+        receipt.receiver_id.as_str(),
+    ])
+}
+
+/// Fills ReceiptsCache with the receipt-transaction pairs stored for `blocks_count` blocks
+/// right before `start_block_height`, so the blocks after the restart don't go to the database.
+/// The newest pairs are loaded, at most the capacity of the cache.
+/// Returns the number of loaded entries
+pub(crate) async fn warm_up_receipts_cache(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    start_block_height: u64,
+    blocks_count: u64,
+    receipts_cache: crate::ReceiptsCache,
+) -> anyhow::Result<usize> {
+    let res = models::select_retry_or_panic(
+        pool,
+        "SELECT min(block_timestamp)::text, max(block_timestamp)::text
+                FROM (SELECT block_timestamp FROM blocks
+                      WHERE block_height < $1::numeric
+                      ORDER BY block_height DESC LIMIT $2::bigint) last_blocks",
+        &[start_block_height.to_string(), blocks_count.to_string()],
+    )
+    .await?;
+    let timestamps: Option<(Option<String>, Option<String>)> =
+        res.first().map(|row| (row.get(0), row.get(1)));
+    let params = match timestamps {
+        Some((Some(from_timestamp), Some(to_timestamp))) => [
+            from_timestamp,
+            to_timestamp,
+            receipts_cache.capacity().to_string(),
+        ],
+        // Nothing is indexed yet in this range
+        _ => return Ok(0),
+    };
+
+    let (receipts, data_receipts, produced_receipts) = try_join!(
+        models::select_retry_or_panic(
+            pool,
+            "SELECT receipt_id, originated_from_transaction_hash, block_timestamp::text
+                    FROM action_receipts
+                    WHERE block_timestamp >= $1::numeric AND block_timestamp <= $2::numeric
+                    ORDER BY block_timestamp DESC LIMIT $3::bigint",
+            &params,
+        ),
+        models::select_retry_or_panic(
+            pool,
+            "SELECT action_receipts__outputs.output_data_id, action_receipts.originated_from_transaction_hash, action_receipts__outputs.block_timestamp::text
+                    FROM action_receipts__outputs JOIN action_receipts ON action_receipts__outputs.receipt_id = action_receipts.receipt_id
+                    WHERE action_receipts__outputs.block_timestamp >= $1::numeric AND action_receipts__outputs.block_timestamp <= $2::numeric
+                    ORDER BY action_receipts__outputs.block_timestamp DESC LIMIT $3::bigint",
+            &params,
+        ),
+        models::select_retry_or_panic(
+            pool,
+            "SELECT execution_outcomes__receipts.produced_receipt_id, action_receipts.originated_from_transaction_hash, execution_outcomes__receipts.block_timestamp::text
+                    FROM execution_outcomes__receipts JOIN action_receipts ON execution_outcomes__receipts.executed_receipt_id = action_receipts.receipt_id
+                    WHERE execution_outcomes__receipts.block_timestamp >= $1::numeric AND execution_outcomes__receipts.block_timestamp <= $2::numeric
+                    ORDER BY execution_outcomes__receipts.block_timestamp DESC LIMIT $3::bigint",
+            &params,
+        ),
+    )?;
+
+    let to_pairs =
+        |rows: Vec<sqlx::postgres::PgRow>,
+         to_key: fn(near_indexer_primitives::CryptoHash) -> crate::ReceiptOrDataId| {
+            rows.into_iter()
+                .map(|q| (q.get(0), q.get(1), q.get(2)))
+                .map(
+                    move |(id_string, transaction_hash_string, block_timestamp): (
+                        String,
+                        String,
+                        String,
+                    )| {
+                        (
+                            u64::from_str(&block_timestamp)
+                                .expect("Failed to convert block_timestamp to u64"),
+                            to_key(
+                                near_indexer_primitives::CryptoHash::from_str(&id_string)
+                                    .expect("Failed to convert String to CryptoHash"),
+                            ),
+                            transaction_hash_string,
+                        )
+                    },
+                )
+        };
+
+    let mut pairs: Vec<_> = to_pairs(receipts, crate::ReceiptOrDataId::ReceiptId)
+        .chain(to_pairs(
+            produced_receipts,
+            crate::ReceiptOrDataId::ReceiptId,
+        ))
+        .chain(to_pairs(data_receipts, crate::ReceiptOrDataId::DataId))
+        .collect();
+    // Keep only the newest pairs which fit into the cache
+    pairs.sort_by_key(|(block_timestamp, _, _)| std::cmp::Reverse(*block_timestamp));
+    pairs.truncate(receipts_cache.capacity());
+    let loaded = pairs.len();
+    receipts_cache
+        .set_many(
+            pairs
+                .into_iter()
+                .rev()
+                .map(|(_, key, transaction_hash)| (key, transaction_hash))
+                .collect(),
+        )
+        .await;
+
+    Ok(loaded)
+}
+
 async fn find_transaction_hashes_for_data_receipts(
     pool: &sqlx::Pool<sqlx::Postgres>,
     data_ids: &[String],
@@ -355,6 +469,7 @@ async fn store_receipt_actions(
     receipts: Vec<(usize, &String, &near_indexer_primitives::views::ReceiptView)>,
     block_header: &near_indexer_primitives::views::BlockHeaderView,
     chunk_header: &near_indexer_primitives::views::ChunkHeaderView,
+    args_limit: models::function_calls::ArgsLimit,
 ) -> anyhow::Result<()> {
     let receipt_actions: Vec<models::ActionReceipt> = receipts
         .iter()
@@ -401,6 +516,33 @@ async fn store_receipt_actions(
         })
         .collect();
 
+    // The same enumeration as for `receipt_action_actions`, so `index_in_chunk` points to the action
+    let function_calls: Vec<models::FunctionCall> = receipts
+        .iter()
+        .filter_map(|(_, _, receipt)| {
+            if let near_indexer_primitives::views::ReceiptEnumView::Action { actions, .. } =
+                &receipt.receipt
+            {
+                Some(actions.iter().map(move |action| (*receipt, action)))
+            } else {
+                None
+            }
+        })
+        .flatten()
+        .enumerate()
+        .filter_map(|(i, (receipt, action))| {
+            models::FunctionCall::try_from_action_view(
+                action,
+                receipt,
+                block_header.timestamp,
+                chunk_header.shard_id as i32,
+                i as i32,
+                args_limit,
+            )
+            .transpose()
+        })
+        .collect::<anyhow::Result<_>>()?;
+
     let receipt_action_output_data: Vec<models::ActionReceiptsOutput> = receipts
         .iter()
         .filter_map(|(_, _, receipt)| {
@@ -436,6 +578,7 @@ async fn store_receipt_actions(
     models::chunked_insert(pool, &receipt_actions).await?;
     try_join!(
         models::chunked_insert(pool, &receipt_action_actions),
+        models::chunked_insert(pool, &function_calls),
         models::chunked_insert(pool, &receipt_action_output_data),
     )?;
 
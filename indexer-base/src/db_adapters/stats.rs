@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use bigdecimal::BigDecimal;
+use near_lake_framework::near_indexer_primitives;
+use sqlx::Arguments;
+
+use crate::models;
+
+/// Saves `block_stats` for the block and adds it to `daily_stats` (UTC days).
+/// The block is added to `daily_stats` only if its `block_stats` row is inserted right now,
+/// so reprocessing of the same block does not count it twice
+pub(crate) async fn store_stats(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+) -> anyhow::Result<()> {
+    let block_header = &streamer_message.block.header;
+
+    let mut transactions_count: i32 = 0;
+    let mut receipts_count: i32 = 0;
+    let mut tokens_burnt: u128 = 0;
+    let mut signers: HashSet<String> = HashSet::new();
+    for shard in &streamer_message.shards {
+        if let Some(chunk) = &shard.chunk {
+            transactions_count += chunk.transactions.len() as i32;
+            for transaction in &chunk.transactions {
+                signers.insert(transaction.transaction.signer_id.to_string());
+                tokens_burnt += transaction.outcome.execution_outcome.outcome.tokens_burnt;
+            }
+        }
+        receipts_count += shard.receipt_execution_outcomes.len() as i32;
+        for outcome in &shard.receipt_execution_outcomes {
+            tokens_burnt += outcome.execution_outcome.outcome.tokens_burnt;
+        }
+    }
+
+    // The chunk header is repeated in the next blocks if the chunk is missing, its gas is not used in this block
+    let (gas_used_by_shard, gas_limit_by_shard): (Vec<i64>, Vec<i64>) = streamer_message
+        .block
+        .chunks
+        .iter()
+        .map(|chunk| {
+            let gas_used = if chunk.height_included == block_header.height {
+                chunk.gas_used
+            } else {
+                0
+            };
+            (gas_used as i64, chunk.gas_limit as i64)
+        })
+        .unzip();
+    let gas_used: i64 = gas_used_by_shard.iter().sum();
+    let gas_limit: i64 = gas_limit_by_shard.iter().sum();
+
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(BigDecimal::from(block_header.height));
+    args.add(block_header.hash.to_string());
+    args.add(BigDecimal::from(block_header.timestamp));
+    args.add(transactions_count);
+    args.add(receipts_count);
+    args.add(BigDecimal::from(gas_used));
+    args.add(BigDecimal::from(gas_limit));
+    args.add(gas_used_by_shard);
+    args.add(gas_limit_by_shard);
+    args.add(models::u128_to_bigdecimal(tokens_burnt));
+    args.add(signers.len() as i32);
+    args.add(signers.into_iter().collect::<Vec<String>>());
+
+    sqlx::query_with(
+        r#"
+WITH inserted AS (
+    INSERT INTO block_stats VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+    ON CONFLICT DO NOTHING
+    RETURNING *, (to_timestamp((block_timestamp / 1000000000)::double precision) AT TIME ZONE 'UTC')::date AS date
+), new_signers AS (
+    INSERT INTO daily_signers
+    SELECT inserted.date, signers.account_id FROM inserted, unnest($12::text[]) AS signers (account_id)
+    ON CONFLICT DO NOTHING
+    RETURNING 1
+)
+INSERT INTO daily_stats
+SELECT date, 1, transactions_count, receipts_count, gas_used, gas_limit, tokens_burnt, (SELECT count(*) FROM new_signers)
+FROM inserted
+ON CONFLICT (date) DO UPDATE
+    SET blocks_count = daily_stats.blocks_count + EXCLUDED.blocks_count,
+        transactions_count = daily_stats.transactions_count + EXCLUDED.transactions_count,
+        receipts_count = daily_stats.receipts_count + EXCLUDED.receipts_count,
+        gas_used = daily_stats.gas_used + EXCLUDED.gas_used,
+        gas_limit = daily_stats.gas_limit + EXCLUDED.gas_limit,
+        tokens_burnt = daily_stats.tokens_burnt + EXCLUDED.tokens_burnt,
+        unique_signers = daily_stats.unique_signers + EXCLUDED.unique_signers
+        "#,
+        args,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use near_lake_framework::near_indexer_primitives;
+use sqlx::Arguments;
+
+use crate::models;
+
+/// Adds the block to the per-epoch and per-day (UTC) rows of `supply_series`.
+/// `tokens_burnt` is summed from the execution outcomes of the block,
+/// `minted` is the sum of the protocol rewards given in the block (see `minted_by_rewards`),
+/// so `is_consistent` compares the supply change with the independently counted mint and burn
+pub(crate) async fn store_supply(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+) -> anyhow::Result<()> {
+    let block_header = &streamer_message.block.header;
+
+    let mut tokens_burnt: u128 = 0;
+    for shard in &streamer_message.shards {
+        if let Some(chunk) = &shard.chunk {
+            for transaction in &chunk.transactions {
+                tokens_burnt += transaction.outcome.execution_outcome.outcome.tokens_burnt;
+            }
+        }
+        for outcome in &shard.receipt_execution_outcomes {
+            tokens_burnt += outcome.execution_outcome.outcome.tokens_burnt;
+        }
+    }
+    let chunks_balance_burnt: u128 = streamer_message
+        .block
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.height_included == block_header.height)
+        .map(|chunk| chunk.balance_burnt)
+        .sum();
+
+    let minted = minted_by_rewards(pool, streamer_message).await?;
+
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(BigDecimal::from(block_header.height));
+    args.add(models::u128_to_bigdecimal(block_header.total_supply));
+    args.add(block_header.prev_hash.to_string());
+    args.add(models::u128_to_bigdecimal(tokens_burnt));
+    args.add(models::u128_to_bigdecimal(chunks_balance_burnt));
+    args.add(models::u128_to_bigdecimal(block_header.gas_price));
+    args.add(block_header.epoch_id.to_string());
+    args.add(BigDecimal::from(block_header.timestamp));
+    args.add(minted);
+
+    sqlx::query_with(
+        r#"
+WITH registered AS (
+    INSERT INTO supply_series_blocks VALUES ($1::numeric)
+    ON CONFLICT DO NOTHING
+    RETURNING block_height
+), prev AS (
+    -- The first indexed block has no previous one, we suppose nothing was minted there
+    SELECT COALESCE(
+        (SELECT total_supply FROM blocks WHERE block_hash = $3),
+        $2::numeric + $5::numeric
+    ) AS total_supply
+)
+INSERT INTO supply_series
+SELECT periods.period_kind, periods.period_id, $1::numeric, $1::numeric, prev.total_supply, $2::numeric,
+       $4::numeric, $5::numeric, $9::numeric, $6::numeric, $6::numeric
+FROM registered, prev, (VALUES
+    ('EPOCH', $7::text),
+    ('DAY', to_char(to_timestamp(($8::numeric / 1000000000)::double precision) AT TIME ZONE 'UTC', 'YYYY-MM-DD'))
+) AS periods (period_kind, period_id)
+ON CONFLICT (period_kind, period_id) DO UPDATE
+    SET start_block_height = LEAST(supply_series.start_block_height, EXCLUDED.start_block_height),
+        start_total_supply = CASE WHEN EXCLUDED.start_block_height < supply_series.start_block_height
+            THEN EXCLUDED.start_total_supply ELSE supply_series.start_total_supply END,
+        end_block_height = GREATEST(supply_series.end_block_height, EXCLUDED.end_block_height),
+        end_total_supply = CASE WHEN EXCLUDED.end_block_height > supply_series.end_block_height
+            THEN EXCLUDED.end_total_supply ELSE supply_series.end_total_supply END,
+        tokens_burnt = supply_series.tokens_burnt + EXCLUDED.tokens_burnt,
+        chunks_balance_burnt = supply_series.chunks_balance_burnt + EXCLUDED.chunks_balance_burnt,
+        minted = supply_series.minted + EXCLUDED.minted,
+        min_gas_price = LEAST(supply_series.min_gas_price, EXCLUDED.min_gas_price),
+        max_gas_price = GREATEST(supply_series.max_gas_price, EXCLUDED.max_gas_price)
+        "#,
+        args,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The tokens are minted only in the first block of an epoch: the protocol rewards of the previous epoch
+/// are added to the validators and the protocol treasury with `ValidatorAccountsUpdate` state changes.
+/// The reward of the account is the growth of its `nonstaked + staked` balance compared to its last
+/// `account_changes` row, the unlocked stake moves between the balances and does not count.
+/// Returns None if any rewarded account has no stored changes before the block
+/// (the first epoch of the indexing or the account is filtered out), the mint is unknown then
+async fn minted_by_rewards(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+) -> anyhow::Result<Option<BigDecimal>> {
+    let mut rewarded_balances: HashMap<String, u128> = HashMap::new();
+    for shard in &streamer_message.shards {
+        for state_change in &shard.state_changes {
+            if let (
+                near_indexer_primitives::views::StateChangeCauseView::ValidatorAccountsUpdate,
+                near_indexer_primitives::views::StateChangeValueView::AccountUpdate {
+                    account_id,
+                    account,
+                },
+            ) = (&state_change.cause, &state_change.value)
+            {
+                // The later changes of the account in the block are not the reward
+                rewarded_balances
+                    .entry(account_id.to_string())
+                    .or_insert(account.amount + account.locked);
+            }
+        }
+    }
+    if rewarded_balances.is_empty() {
+        return Ok(Some(BigDecimal::from(0)));
+    }
+
+    let account_ids: Vec<String> = rewarded_balances.keys().cloned().collect();
+    let previous_balances: HashMap<String, BigDecimal> = sqlx::query_as::<_, (String, BigDecimal)>(
+        r#"
+SELECT DISTINCT ON (account_id) account_id, nonstaked_balance + staked_balance
+FROM account_changes
+WHERE account_id = ANY($1) AND block_timestamp < $2::numeric
+ORDER BY account_id, block_timestamp DESC, chunk_index_in_block DESC, index_in_chunk DESC
+        "#,
+    )
+    .bind(&account_ids)
+    .bind(BigDecimal::from(streamer_message.block.header.timestamp))
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut minted = BigDecimal::from(0);
+    for (account_id, balance) in rewarded_balances {
+        match previous_balances.get(&account_id) {
+            Some(previous_balance) => {
+                minted += models::u128_to_bigdecimal(balance) - previous_balance;
+            }
+            None => {
+                tracing::warn!(
+                    target: crate::LOGGING_PREFIX,
+                    "The minted amount at block {} is unknown, {} has no stored balance before it",
+                    streamer_message.block.header.height,
+                    account_id
+                );
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(minted))
+}
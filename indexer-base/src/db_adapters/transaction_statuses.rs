@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+
+use near_lake_framework::near_indexer_primitives::{
+    self,
+    views::{ExecutionOutcomeWithIdView, ExecutionStatusView},
+};
+use sqlx::Row;
+use tokio::sync::Mutex;
+
+use crate::models;
+
+// The transactions which are not completed during this amount of blocks are not tracked anymore
+const MAX_OPEN_BLOCKS: u64 = 10_000;
+const PRUNING_PERIOD_BLOCKS: u64 = 1_000;
+
+const SUCCESS: &str = "SUCCESS";
+const FAILURE: &str = "FAILURE";
+
+#[derive(Debug)]
+struct OpenTransaction {
+    outstanding_receipts: usize,
+    gas_burnt: u64,
+    tokens_burnt: u128,
+    // The status of the transaction is the status of the last outcome in the chain of `SuccessReceiptId`
+    // links started by the transaction outcome. It's the Receipt which outcome we are waiting for
+    status_receipt_id: Option<near_indexer_primitives::CryptoHash>,
+    final_status: Option<&'static str>,
+    started_at_block_height: u64,
+}
+
+/// Tracks the transactions which still have the Receipts to be executed.
+/// The state is kept in memory and persisted to `open_transactions` and `open_transactions_receipts`
+/// with each block, so it survives restarts. The blocks which are already applied to the state
+/// (e.g. rewound on restart) are skipped.
+/// The transactions started before the first indexed block are not tracked, their `final_status` stays NULL
+#[derive(Debug, Default)]
+pub(crate) struct OpenTransactions {
+    transactions: HashMap<String, OpenTransaction>,
+    // Outstanding Receipt ID to its parent Transaction hash
+    receipts: HashMap<near_indexer_primitives::CryptoHash, String>,
+    last_block_height: Option<u64>,
+}
+
+/// The changes of [OpenTransactions] made by one block, they are persisted together
+#[derive(Debug, Default)]
+struct BlockChanges {
+    completed: Vec<models::TransactionFinalStatus>,
+    touched_transactions: HashSet<String>,
+    removed_transactions: HashSet<String>,
+    added_receipts: HashMap<near_indexer_primitives::CryptoHash, String>,
+    resolved_receipts: HashSet<near_indexer_primitives::CryptoHash>,
+}
+
+impl OpenTransactions {
+    /// Restores the state persisted by the previous run
+    pub(crate) async fn load(pool: &sqlx::Pool<sqlx::Postgres>) -> anyhow::Result<Self> {
+        let last_block_height: Option<BigDecimal> =
+            sqlx::query_scalar("SELECT last_block_height FROM open_transactions_progress")
+                .fetch_optional(pool)
+                .await?;
+        let mut open_transactions = Self {
+            last_block_height: last_block_height.and_then(|height| height.to_u64()),
+            ..Self::default()
+        };
+
+        for row in sqlx::query(
+            "SELECT transaction_hash, outstanding_receipts, gas_burnt::text, tokens_burnt::text,
+                    status_receipt_id, final_status, started_at_block_height::text
+             FROM open_transactions",
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            let status_receipt_id: Option<String> = row.get(4);
+            let final_status: Option<String> = row.get(5);
+            open_transactions.transactions.insert(
+                row.get(0),
+                OpenTransaction {
+                    outstanding_receipts: usize::try_from(row.get::<i32, _>(1))?,
+                    gas_burnt: u64::from_str(row.get(2))?,
+                    tokens_burnt: u128::from_str(row.get(3))?,
+                    status_receipt_id: status_receipt_id
+                        .map(|receipt_id| {
+                            near_indexer_primitives::CryptoHash::from_str(&receipt_id)
+                        })
+                        .transpose()
+                        .map_err(|err| anyhow::anyhow!("Invalid status_receipt_id: {}", err))?,
+                    final_status: match final_status.as_deref() {
+                        Some(SUCCESS) => Some(SUCCESS),
+                        Some(FAILURE) => Some(FAILURE),
+                        _ => None,
+                    },
+                    started_at_block_height: u64::from_str(row.get(6))?,
+                },
+            );
+        }
+
+        for row in
+            sqlx::query("SELECT receipt_id, transaction_hash FROM open_transactions_receipts")
+                .fetch_all(pool)
+                .await?
+        {
+            let receipt_id: String = row.get(0);
+            open_transactions.receipts.insert(
+                near_indexer_primitives::CryptoHash::from_str(&receipt_id)
+                    .map_err(|err| anyhow::anyhow!("Invalid receipt_id: {}", err))?,
+                row.get(1),
+            );
+        }
+
+        tracing::info!(
+            target: crate::LOGGING_PREFIX,
+            "Loaded {} open transaction(s) with {} outstanding Receipt(s), applied up to block {:?}",
+            open_transactions.transactions.len(),
+            open_transactions.receipts.len(),
+            open_transactions.last_block_height
+        );
+        Ok(open_transactions)
+    }
+
+    fn open(
+        &mut self,
+        transaction: &near_indexer_primitives::IndexerTransactionWithOutcome,
+        block_height: u64,
+        changes: &mut BlockChanges,
+    ) {
+        let transaction_hash = transaction.transaction.hash.to_string();
+        self.transactions.insert(
+            transaction_hash.clone(),
+            OpenTransaction {
+                // the transaction itself is the first outstanding item
+                outstanding_receipts: 1,
+                gas_burnt: 0,
+                tokens_burnt: 0,
+                status_receipt_id: None,
+                final_status: None,
+                started_at_block_height: block_height,
+            },
+        );
+        self.apply_outcome(
+            transaction_hash,
+            &transaction.outcome.execution_outcome,
+            true,
+            block_height,
+            changes,
+        );
+    }
+
+    fn resolve_receipt(
+        &mut self,
+        outcome: &ExecutionOutcomeWithIdView,
+        block_height: u64,
+        changes: &mut BlockChanges,
+    ) {
+        let transaction_hash = match self.receipts.remove(&outcome.id) {
+            Some(transaction_hash) => transaction_hash,
+            None => return,
+        };
+        if changes.added_receipts.remove(&outcome.id).is_none() {
+            changes.resolved_receipts.insert(outcome.id);
+        }
+        self.apply_outcome(transaction_hash, outcome, false, block_height, changes);
+    }
+
+    fn apply_outcome(
+        &mut self,
+        transaction_hash: String,
+        outcome: &ExecutionOutcomeWithIdView,
+        is_transaction_outcome: bool,
+        block_height: u64,
+        changes: &mut BlockChanges,
+    ) {
+        let transaction = match self.transactions.get_mut(&transaction_hash) {
+            Some(transaction) => transaction,
+            None => return,
+        };
+        transaction.gas_burnt += outcome.outcome.gas_burnt;
+        transaction.tokens_burnt += outcome.outcome.tokens_burnt;
+        // The failures of the other Receipts (e.g. the failed cross-contract call handled by the callback)
+        // do not affect the status of the transaction
+        if is_transaction_outcome || transaction.status_receipt_id == Some(outcome.id) {
+            match &outcome.outcome.status {
+                ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+                    transaction.status_receipt_id = Some(*receipt_id)
+                }
+                ExecutionStatusView::SuccessValue(_) => {
+                    transaction.status_receipt_id = None;
+                    transaction.final_status = Some(SUCCESS);
+                }
+                ExecutionStatusView::Failure(_) => {
+                    transaction.status_receipt_id = None;
+                    transaction.final_status = Some(FAILURE);
+                }
+                ExecutionStatusView::Unknown => {}
+            }
+        }
+        transaction.outstanding_receipts += outcome.outcome.receipt_ids.len();
+        transaction.outstanding_receipts -= 1;
+        for receipt_id in &outcome.outcome.receipt_ids {
+            self.receipts.insert(*receipt_id, transaction_hash.clone());
+            changes
+                .added_receipts
+                .insert(*receipt_id, transaction_hash.clone());
+        }
+
+        if transaction.outstanding_receipts > 0 {
+            changes.touched_transactions.insert(transaction_hash);
+            return;
+        }
+        let transaction = match self.transactions.remove(&transaction_hash) {
+            Some(transaction) => transaction,
+            None => return,
+        };
+        changes.touched_transactions.remove(&transaction_hash);
+        changes
+            .removed_transactions
+            .insert(transaction_hash.clone());
+        match transaction.final_status {
+            Some(final_status) => changes.completed.push(models::TransactionFinalStatus {
+                transaction_hash,
+                final_status: final_status.to_string(),
+                total_gas_burnt: transaction.gas_burnt.into(),
+                total_tokens_burnt: models::u128_to_bigdecimal(transaction.tokens_burnt),
+                completed_at_block_height: block_height.into(),
+            }),
+            None => tracing::warn!(
+                target: crate::LOGGING_PREFIX,
+                "All the Receipts of transaction {} are executed, but its status is unknown",
+                transaction_hash
+            ),
+        }
+    }
+
+    fn prune(&mut self, block_height: u64, changes: &mut BlockChanges) {
+        let expired: Vec<String> = self
+            .transactions
+            .iter()
+            .filter(|(_, transaction)| {
+                transaction.started_at_block_height + MAX_OPEN_BLOCKS <= block_height
+            })
+            .map(|(transaction_hash, _)| transaction_hash.clone())
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        for transaction_hash in &expired {
+            self.transactions.remove(transaction_hash);
+            changes.touched_transactions.remove(transaction_hash);
+            changes
+                .removed_transactions
+                .insert(transaction_hash.clone());
+        }
+        let transactions = &self.transactions;
+        self.receipts
+            .retain(|_, transaction_hash| transactions.contains_key(transaction_hash));
+        changes
+            .added_receipts
+            .retain(|_, transaction_hash| transactions.contains_key(transaction_hash));
+        tracing::warn!(
+            target: crate::LOGGING_PREFIX,
+            "{} transaction(s) are not completed during {} blocks, stop tracking them",
+            expired.len(),
+            MAX_OPEN_BLOCKS
+        );
+    }
+}
+
+/// Follows the Receipts tree of the transactions and writes `final_status`,
+/// `total_gas_burnt`, `total_tokens_burnt` and `completed_at_block_height`
+/// when the last Receipt of the transaction is executed.
+/// The blocks should be passed strictly in order
+pub(crate) async fn store_final_statuses(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+    open_transactions: &Mutex<OpenTransactions>,
+) -> anyhow::Result<()> {
+    let block_height = streamer_message.block.header.height;
+    let mut open_transactions = open_transactions.lock().await;
+    if open_transactions
+        .last_block_height
+        .map_or(false, |last_block_height| block_height <= last_block_height)
+    {
+        // The block is already applied before the restart
+        return Ok(());
+    }
+    let mut changes = BlockChanges::default();
+
+    // The transactions go first, their Receipts could be executed in the same block
+    for shard in &streamer_message.shards {
+        if let Some(chunk) = &shard.chunk {
+            for transaction in &chunk.transactions {
+                open_transactions.open(transaction, block_height, &mut changes);
+            }
+        }
+    }
+    for shard in &streamer_message.shards {
+        for outcome in &shard.receipt_execution_outcomes {
+            open_transactions.resolve_receipt(
+                &outcome.execution_outcome,
+                block_height,
+                &mut changes,
+            );
+        }
+    }
+    if block_height % PRUNING_PERIOD_BLOCKS == 0 {
+        open_transactions.prune(block_height, &mut changes);
+    }
+
+    persist_retry_or_panic(pool, block_height, &open_transactions, &changes).await?;
+    open_transactions.last_block_height = Some(block_height);
+    Ok(())
+}
+
+async fn persist_retry_or_panic(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    block_height: u64,
+    open_transactions: &OpenTransactions,
+    changes: &BlockChanges,
+) -> anyhow::Result<()> {
+    let mut interval = crate::INTERVAL;
+    let mut retry_attempt = 0usize;
+
+    loop {
+        if retry_attempt == crate::db_adapters::RETRY_COUNT {
+            return Err(anyhow::anyhow!(
+                "Failed to perform query to database after {} attempts. Stop trying.",
+                crate::db_adapters::RETRY_COUNT
+            ));
+        }
+        retry_attempt += 1;
+
+        match persist(pool, block_height, open_transactions, changes).await {
+            Ok(_) => return Ok(()),
+            Err(async_error) => {
+                tracing::error!(
+                    target: crate::LOGGING_PREFIX,
+                    "Error occurred during {}:\nOpen transactions of block {} were not stored. \n Retrying in {} milliseconds...",
+                    async_error,
+                    block_height,
+                    interval.as_millis(),
+                );
+                tokio::time::sleep(interval).await;
+                if interval < crate::MAX_DELAY_TIME {
+                    interval *= 2;
+                }
+            }
+        }
+    }
+}
+
+/// Stores the changes of the block and the final statuses in one DB transaction,
+/// so the persisted state always matches `last_block_height`
+async fn persist(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    block_height: u64,
+    open_transactions: &OpenTransactions,
+    changes: &BlockChanges,
+) -> anyhow::Result<()> {
+    let removed_transactions: Vec<String> = changes.removed_transactions.iter().cloned().collect();
+    let resolved_receipts: Vec<String> = changes
+        .resolved_receipts
+        .iter()
+        .map(|receipt_id| receipt_id.to_string())
+        .collect();
+
+    let mut transaction_hashes = vec![];
+    let mut outstanding_receipts = vec![];
+    let mut gas_burnt = vec![];
+    let mut tokens_burnt = vec![];
+    let mut status_receipt_ids = vec![];
+    let mut final_statuses = vec![];
+    let mut started_at_block_heights = vec![];
+    for transaction_hash in &changes.touched_transactions {
+        if let Some(transaction) = open_transactions.transactions.get(transaction_hash) {
+            transaction_hashes.push(transaction_hash.clone());
+            outstanding_receipts.push(i32::try_from(transaction.outstanding_receipts)?);
+            gas_burnt.push(transaction.gas_burnt.to_string());
+            tokens_burnt.push(transaction.tokens_burnt.to_string());
+            // Empty strings are stored as NULL
+            status_receipt_ids.push(
+                transaction
+                    .status_receipt_id
+                    .map(|receipt_id| receipt_id.to_string())
+                    .unwrap_or_default(),
+            );
+            final_statuses.push(transaction.final_status.unwrap_or_default().to_string());
+            started_at_block_heights.push(transaction.started_at_block_height.to_string());
+        }
+    }
+    let (added_receipt_ids, added_receipt_transactions): (Vec<String>, Vec<String>) = changes
+        .added_receipts
+        .iter()
+        .map(|(receipt_id, transaction_hash)| (receipt_id.to_string(), transaction_hash.clone()))
+        .unzip();
+
+    let mut db_transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM open_transactions WHERE transaction_hash = ANY($1)")
+        .bind(&removed_transactions)
+        .execute(&mut db_transaction)
+        .await?;
+    sqlx::query(
+        "DELETE FROM open_transactions_receipts WHERE transaction_hash = ANY($1) OR receipt_id = ANY($2)",
+    )
+    .bind(&removed_transactions)
+    .bind(&resolved_receipts)
+    .execute(&mut db_transaction)
+    .await?;
+    sqlx::query(
+        r#"
+INSERT INTO open_transactions
+SELECT transaction_hash, outstanding_receipts, gas_burnt::numeric, tokens_burnt::numeric,
+       NULLIF(status_receipt_id, ''), NULLIF(final_status, ''), started_at_block_height::numeric
+FROM unnest($1::text[], $2::integer[], $3::text[], $4::text[], $5::text[], $6::text[], $7::text[])
+    AS t (transaction_hash, outstanding_receipts, gas_burnt, tokens_burnt, status_receipt_id, final_status, started_at_block_height)
+ON CONFLICT (transaction_hash) DO UPDATE
+    SET outstanding_receipts = EXCLUDED.outstanding_receipts,
+        gas_burnt = EXCLUDED.gas_burnt,
+        tokens_burnt = EXCLUDED.tokens_burnt,
+        status_receipt_id = EXCLUDED.status_receipt_id,
+        final_status = EXCLUDED.final_status
+        "#,
+    )
+    .bind(&transaction_hashes)
+    .bind(&outstanding_receipts)
+    .bind(&gas_burnt)
+    .bind(&tokens_burnt)
+    .bind(&status_receipt_ids)
+    .bind(&final_statuses)
+    .bind(&started_at_block_heights)
+    .execute(&mut db_transaction)
+    .await?;
+    sqlx::query(
+        r#"
+INSERT INTO open_transactions_receipts
+SELECT * FROM unnest($1::text[], $2::text[])
+ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(&added_receipt_ids)
+    .bind(&added_receipt_transactions)
+    .execute(&mut db_transaction)
+    .await?;
+    for items in changes
+        .completed
+        .chunks(crate::db_adapters::CHUNK_SIZE_FOR_BATCH_INSERT)
+    {
+        let mut args = sqlx::postgres::PgArguments::default();
+        for item in items {
+            models::SqlMethods::add_to_args(item, &mut args);
+        }
+        sqlx::query_with(
+            &<models::TransactionFinalStatus as models::SqlMethods>::insert_query(items.len())?,
+            args,
+        )
+        .execute(&mut db_transaction)
+        .await?;
+    }
+    sqlx::query(
+        r#"
+INSERT INTO open_transactions_progress VALUES (TRUE, $1::numeric)
+ON CONFLICT (id) DO UPDATE SET last_block_height = EXCLUDED.last_block_height
+        "#,
+    )
+    .bind(block_height.to_string())
+    .execute(&mut db_transaction)
+    .await?;
+    db_transaction.commit().await?;
+    Ok(())
+}
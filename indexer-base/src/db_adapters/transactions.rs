@@ -1,4 +1,3 @@
-use cached::Cached;
 use futures::future::try_join_all;
 
 use near_lake_framework::near_indexer_primitives;
@@ -11,6 +10,7 @@ pub(crate) async fn store_transactions(
     block_hash: &near_indexer_primitives::CryptoHash,
     block_timestamp: u64,
     receipts_cache: crate::ReceiptsCache,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
     let tx_futures = shards
         .iter()
@@ -24,6 +24,7 @@ pub(crate) async fn store_transactions(
                 block_timestamp,
                 &chunk.header,
                 std::sync::Arc::clone(&receipts_cache),
+                account_filter,
             )
         });
 
@@ -38,12 +39,13 @@ async fn store_chunk_transactions(
     block_timestamp: u64,
     chunk_view: &near_indexer_primitives::views::ChunkHeaderView,
     receipts_cache: crate::ReceiptsCache,
+    account_filter: &crate::account_filter::AccountFilter,
 ) -> anyhow::Result<()> {
-    let mut receipts_cache_lock = receipts_cache.lock().await;
+    let mut receipts_cache_pairs = vec![];
     let transaction_models = transactions
         .iter()
         .enumerate()
-        .map(|(i, transaction)| {
+        .filter_map(|(i, transaction)| {
             let converted_into_receipt_id = transaction
                 .outcome
                 .execution_outcome
@@ -57,12 +59,20 @@ async fn store_chunk_transactions(
             // and the Transaction hash as a value.
             // Later, while Receipt will be looking for a parent Transaction hash
             // it will be able to find it in the ReceiptsCache
-            receipts_cache_lock.cache_set(
+            // We do it even for the filtered out transactions, their Receipts may involve the filtered in accounts
+            receipts_cache_pairs.push((
                 crate::ReceiptOrDataId::ReceiptId(*converted_into_receipt_id),
                 transaction.transaction.hash.to_string(),
-            );
+            ));
 
-            models::Transaction::from_indexer_transaction(
+            if !account_filter.is_any_allowed([
+                transaction.transaction.signer_id.as_str(),
+                transaction.transaction.receiver_id.as_str(),
+            ]) {
+                return None;
+            }
+
+            Some(models::Transaction::from_indexer_transaction(
                 transaction,
                 &transaction.transaction.hash.to_string(),
                 &converted_into_receipt_id.to_string(),
@@ -70,10 +80,10 @@ async fn store_chunk_transactions(
                 block_timestamp,
                 chunk_view,
                 i as i32,
-            )
+            ))
         })
         .collect::<Vec<models::Transaction>>();
-    drop(receipts_cache_lock);
+    receipts_cache.set_many(receipts_cache_pairs).await;
 
     models::chunked_insert(pool, &transaction_models).await?;
 
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use near_lake_framework::near_indexer_primitives;
+use sqlx::Arguments;
+
+#[derive(Debug, Default)]
+struct Production {
+    blocks_produced: i32,
+    chunks_produced: i32,
+    gas_used: u64,
+}
+
+/// Adds the block to `validator_production` (per author and epoch) and its missing chunks to `epoch_missed_chunks`.
+/// The block is counted only once: the first statement registers the block height,
+/// the aggregates are not touched if the block was already registered
+pub(crate) async fn store_validator_production(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+) -> anyhow::Result<()> {
+    let block = &streamer_message.block;
+
+    let mut production: HashMap<String, Production> = HashMap::new();
+    production
+        .entry(block.author.to_string())
+        .or_default()
+        .blocks_produced += 1;
+    // The chunk authors are known only from the shards, the chunk headers of the block don't have them
+    let chunk_authors: HashMap<u64, String> = streamer_message
+        .shards
+        .iter()
+        .filter_map(|shard| {
+            shard
+                .chunk
+                .as_ref()
+                .map(|chunk| (shard.shard_id, chunk.author.to_string()))
+        })
+        .collect();
+    // Same as `block_stats`: the chunk header is repeated in the next blocks if the chunk is missing.
+    // The producer of the missing chunk is not included into the block, we count them per shard
+    let mut missed_shards: Vec<i32> = vec![];
+    for chunk in &block.chunks {
+        if chunk.height_included != block.header.height {
+            missed_shards.push(chunk.shard_id as i32);
+            continue;
+        }
+        match chunk_authors.get(&chunk.shard_id) {
+            Some(author) => {
+                let author = production.entry(author.clone()).or_default();
+                author.chunks_produced += 1;
+                author.gas_used += chunk.gas_used;
+            }
+            None => tracing::warn!(
+                target: crate::LOGGING_PREFIX,
+                "Chunk of shard {} is included into block {}, but the shard has no chunk",
+                chunk.shard_id,
+                block.header.height
+            ),
+        }
+    }
+
+    let mut account_ids: Vec<String> = vec![];
+    let mut blocks_produced: Vec<i32> = vec![];
+    let mut chunks_produced: Vec<i32> = vec![];
+    let mut gas_used: Vec<BigDecimal> = vec![];
+    for (account_id, item) in production {
+        account_ids.push(account_id);
+        blocks_produced.push(item.blocks_produced);
+        chunks_produced.push(item.chunks_produced);
+        gas_used.push(item.gas_used.into());
+    }
+
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(BigDecimal::from(block.header.height));
+    args.add(block.header.epoch_id.to_string());
+    args.add(account_ids);
+    args.add(blocks_produced);
+    args.add(chunks_produced);
+    args.add(gas_used);
+    args.add(missed_shards);
+
+    sqlx::query_with(
+        r#"
+WITH registered AS (
+    INSERT INTO validator_production_blocks VALUES ($1, $2)
+    ON CONFLICT DO NOTHING
+    RETURNING epoch_id
+), production AS (
+    INSERT INTO validator_production
+    SELECT registered.epoch_id, items.account_id, items.blocks_produced, items.chunks_produced, items.gas_used
+    FROM registered, unnest($3::text[], $4::integer[], $5::integer[], $6::numeric[])
+        AS items (account_id, blocks_produced, chunks_produced, gas_used)
+    ON CONFLICT (epoch_id, account_id) DO UPDATE
+        SET blocks_produced = validator_production.blocks_produced + EXCLUDED.blocks_produced,
+            chunks_produced = validator_production.chunks_produced + EXCLUDED.chunks_produced,
+            gas_used = validator_production.gas_used + EXCLUDED.gas_used
+)
+INSERT INTO epoch_missed_chunks
+SELECT registered.epoch_id, missed.shard_id, 1
+FROM registered, unnest($7::integer[]) AS missed (shard_id)
+ON CONFLICT (epoch_id, shard_id) DO UPDATE
+    SET chunks_missed = epoch_missed_chunks.chunks_missed + 1
+        "#,
+        args,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,13 +1,14 @@
-use cached::SizedCache;
 use futures::{try_join, StreamExt};
 use tokio::sync::Mutex;
 
-use indexer_opts::Parser;
 use near_lake_framework::near_indexer_primitives;
 
+mod account_filter;
+mod audit;
 mod configs;
 mod db_adapters;
 mod models;
+mod receipts_cache;
 
 pub(crate) const LOGGING_PREFIX: &str = "indexer_base";
 
@@ -25,18 +26,44 @@ pub type ParentTransactionHashString = String;
 // touching the database
 // The key is ReceiptID
 // The value is TransactionHash (the very parent of the Receipt)
-pub type ReceiptsCache =
-    std::sync::Arc<Mutex<SizedCache<ReceiptOrDataId, ParentTransactionHashString>>>;
+// The cache could be backed by the on-disk store, see `--receipts-store-path`
+pub type ReceiptsCache = std::sync::Arc<receipts_cache::ReceiptsIndex>;
+
+/// The settings and the state shared by the handlers of all the blocks
+pub(crate) struct IndexingContext {
+    pub strict_mode: bool,
+    pub account_filter: account_filter::AccountFilter,
+    // Opt-in: the storage changes are stored only for the watched contracts
+    pub watched_contracts: account_filter::AccountFilter,
+    pub args_limit: models::function_calls::ArgsLimit,
+    pub receipts_cache: ReceiptsCache,
+    // Tracks the Receipts tree of the transactions to compute their final status
+    pub open_transactions: Mutex<db_adapters::transaction_statuses::OpenTransactions>,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
-    let opts = indexer_opts::Opts::parse();
+    let opts = indexer_opts::Opts::parse_with_config(env!("CARGO_PKG_NAME"))?;
     let _worker_guard = configs::init_tracing(opts.debug)?;
 
     let pool = sqlx::PgPool::connect(&opts.database_url).await?;
-    let lake_config = opts.to_lake_config(&pool).await?;
-    let (sender, stream) = near_lake_framework::streamer(lake_config);
+    if let Some(indexer_opts::Command::Audit(args)) = &opts.command {
+        return audit::run(&opts, args, &pool).await;
+    }
+    let account_filter =
+        account_filter::AccountFilter::new(&opts.allow_accounts, &opts.deny_accounts);
+    if !account_filter.is_empty() {
+        account_filter::check_foreign_keys(&pool).await?;
+        tracing::info!(
+            target: LOGGING_PREFIX,
+            "Indexing only the accounts matching {:?}",
+            account_filter
+        );
+    }
+    let (lake_config, start_block_height) = opts
+        .to_lake_config_with_start_block_height(&pool, env!("CARGO_PKG_VERSION"))
+        .await?;
     let end_block_height = opts.end_block_height.unwrap_or(u64::MAX);
 
     // We want to prevent unnecessary SELECT queries to the database to find
@@ -44,18 +71,54 @@ async fn main() -> anyhow::Result<()> {
     // Later we need to find the Receipt which is a parent to underlying Receipts.
     // Receipt ID will of the child will be stored as key and parent Transaction hash/Receipt ID
     // will be stored as a value
+    let receipts_store = match &opts.receipts_store_path {
+        Some(path) => {
+            let store = receipts_cache::ReceiptsStore::open(
+                path,
+                std::time::Duration::from_secs(opts.receipts_store_ttl_hours * 60 * 60),
+            )?;
+            store.spawn_pruning();
+            Some(store)
+        }
+        None => None,
+    };
     let receipts_cache: ReceiptsCache =
-        std::sync::Arc::new(Mutex::new(SizedCache::with_size(100_000)));
+        std::sync::Arc::new(receipts_cache::ReceiptsIndex::new(100_000, receipts_store));
+    if opts.receipts_cache_warm_up_blocks > 0 {
+        let loaded = db_adapters::receipts::warm_up_receipts_cache(
+            &pool,
+            start_block_height,
+            opts.receipts_cache_warm_up_blocks,
+            receipts_cache.clone(),
+        )
+        .await?;
+        tracing::info!(
+            target: LOGGING_PREFIX,
+            "ReceiptsCache is warmed up with {} entries from {} blocks before block {}",
+            loaded,
+            opts.receipts_cache_warm_up_blocks,
+            start_block_height
+        );
+    }
+
+    let context = IndexingContext {
+        strict_mode: true, // !opts.non_strict_mode, // TODO support one more flag
+        account_filter,
+        watched_contracts: account_filter::AccountFilter::new(&opts.watched_contracts, &[]),
+        args_limit: models::function_calls::ArgsLimit {
+            max_size: opts.function_call_args_max_size,
+            large_args_mode: opts.function_call_large_args,
+        },
+        receipts_cache,
+        open_transactions: Mutex::new(
+            db_adapters::transaction_statuses::OpenTransactions::load(&pool).await?,
+        ),
+    };
+
+    let (sender, stream) = near_lake_framework::streamer(lake_config);
 
     let mut handlers = tokio_stream::wrappers::ReceiverStream::new(stream)
-        .map(|streamer_message| {
-            handle_streamer_message(
-                streamer_message,
-                &pool,
-                receipts_cache.clone(),
-                true, // !opts.non_strict_mode, // TODO support one more flag
-            )
-        })
+        .map(|streamer_message| handle_streamer_message(streamer_message, &pool, &context))
         .buffer_unordered(1usize);
 
     while let Some(handle_message) = handlers.next().await {
@@ -93,8 +156,7 @@ async fn main() -> anyhow::Result<()> {
 async fn handle_streamer_message(
     streamer_message: near_indexer_primitives::StreamerMessage,
     pool: &sqlx::Pool<sqlx::Postgres>,
-    receipts_cache: ReceiptsCache,
-    strict_mode: bool,
+    context: &IndexingContext,
 ) -> anyhow::Result<u64> {
     if streamer_message.block.header.height % 100 == 0 {
         eprintln!(
@@ -118,15 +180,15 @@ async fn handle_streamer_message(
         &streamer_message.shards,
         &streamer_message.block.header.hash,
         streamer_message.block.header.timestamp,
-        receipts_cache.clone(),
+        context.receipts_cache.clone(),
+        &context.account_filter,
     );
 
     let receipts_future = db_adapters::receipts::store_receipts(
         pool,
-        strict_mode,
         &streamer_message.shards,
         &streamer_message.block.header,
-        receipts_cache.clone(),
+        context,
     );
 
     let execution_outcomes_future = db_adapters::execution_outcomes::store_execution_outcomes(
@@ -134,7 +196,8 @@ async fn handle_streamer_message(
         &streamer_message.shards,
         &streamer_message.block.header.hash,
         streamer_message.block.header.timestamp,
-        receipts_cache.clone(),
+        context.receipts_cache.clone(),
+        &context.account_filter,
     );
 
     let account_changes_future = db_adapters::account_changes::store_account_changes(
@@ -142,8 +205,31 @@ async fn handle_streamer_message(
         &streamer_message.shards,
         &streamer_message.block.header.hash,
         streamer_message.block.header.timestamp,
+        &context.account_filter,
     );
 
+    let contract_deployments_future = db_adapters::contract_deployments::store_contract_deployments(
+        pool,
+        &streamer_message.shards,
+        &streamer_message.block.header,
+        &context.account_filter,
+    );
+
+    let contract_state_changes_future =
+        db_adapters::contract_state_changes::store_contract_state_changes(
+            pool,
+            &streamer_message.shards,
+            &streamer_message.block.header,
+            &context.watched_contracts,
+        );
+
+    let stats_future = db_adapters::stats::store_stats(pool, &streamer_message);
+
+    let supply_future = db_adapters::supply::store_supply(pool, &streamer_message);
+
+    let validator_production_future =
+        db_adapters::validator_production::store_validator_production(pool, &streamer_message);
+
     blocks_future.await?;
     // FK to block_hash
     chunks_future.await?;
@@ -154,8 +240,21 @@ async fn handle_streamer_message(
     try_join!(
         // this guy depends on transactions and receipts with its FKs
         account_changes_future,
+        contract_deployments_future,
+        contract_state_changes_future,
+        stats_future,
+        validator_production_future,
+        // reads the previous block total supply, so it needs blocks_future finished
+        supply_future,
         // this guy thinks that receipts_future finished, and clears the cache
         execution_outcomes_future
     )?;
+    // updates the stored transactions, so it goes last
+    db_adapters::transaction_statuses::store_final_statuses(
+        pool,
+        &streamer_message,
+        &context.open_transactions,
+    )
+    .await?;
     Ok(streamer_message.block.header.height)
 }
@@ -0,0 +1,85 @@
+use sqlx::Arguments;
+
+use near_lake_framework::near_indexer_primitives;
+
+use crate::models::FieldCount;
+
+#[derive(Debug, sqlx::FromRow, FieldCount)]
+pub struct ContractDeployment {
+    pub account_id: String,
+    pub code_hash: String,
+    pub code_size: i64,
+    pub receipt_id: Option<String>,
+    pub block_height: bigdecimal::BigDecimal,
+    pub block_timestamp: bigdecimal::BigDecimal,
+    pub had_code_before: bool,
+    pub chunk_index_in_block: i32,
+    pub index_in_chunk: i32,
+}
+
+impl ContractDeployment {
+    pub fn from_state_change_with_cause(
+        state_change_with_cause: &near_indexer_primitives::views::StateChangeWithCauseView,
+        block_header: &near_indexer_primitives::views::BlockHeaderView,
+        chunk_index_in_block: i32,
+        index_in_chunk: i32,
+    ) -> Option<Self> {
+        let near_indexer_primitives::views::StateChangeWithCauseView { cause, value } =
+            state_change_with_cause;
+
+        if let near_indexer_primitives::views::StateChangeValueView::ContractCodeUpdate {
+            account_id,
+            code,
+        } = value
+        {
+            Some(Self {
+                account_id: account_id.to_string(),
+                // The same hash is stored in `code_hash` of the account
+                code_hash: near_primitives::hash::hash(code).to_string(),
+                code_size: code.len() as i64,
+                receipt_id: match cause {
+                    near_indexer_primitives::views::StateChangeCauseView::ReceiptProcessing {
+                        receipt_hash,
+                    } => Some(receipt_hash.to_string()),
+                    _ => None,
+                },
+                block_height: block_header.height.into(),
+                block_timestamp: block_header.timestamp.into(),
+                // we fill it later because we need to check the previous deployments
+                had_code_before: false,
+                chunk_index_in_block,
+                index_in_chunk,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl crate::models::SqlMethods for ContractDeployment {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.account_id);
+        args.add(&self.code_hash);
+        args.add(self.code_size);
+        args.add(&self.receipt_id);
+        args.add(&self.block_height);
+        args.add(&self.block_timestamp);
+        args.add(self.had_code_before);
+        args.add(&self.chunk_index_in_block);
+        args.add(&self.index_in_chunk);
+    }
+
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO contract_deployments VALUES ".to_owned()
+            + &crate::models::create_placeholders(items_count, ContractDeployment::field_count())?
+            + " ON CONFLICT DO NOTHING")
+    }
+
+    fn delete_query() -> String {
+        "DELETE FROM contract_deployments WHERE block_timestamp >= $1".to_string()
+    }
+
+    fn name() -> String {
+        "contract_deployments".to_string()
+    }
+}
@@ -0,0 +1,133 @@
+use sqlx::Arguments;
+
+use near_lake_framework::near_indexer_primitives;
+
+use crate::models::{FieldCount, PrintEnum};
+
+#[derive(Debug, sqlx::FromRow, FieldCount)]
+pub struct ContractStateChange {
+    pub account_id: String,
+    pub change_kind: String,
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+    pub caused_by_transaction_hash: Option<String>,
+    pub caused_by_receipt_id: Option<String>,
+    pub update_reason: String,
+    pub block_height: bigdecimal::BigDecimal,
+    pub block_timestamp: bigdecimal::BigDecimal,
+    pub chunk_index_in_block: i32,
+    pub index_in_chunk: i32,
+}
+
+impl ContractStateChange {
+    /// Returns None for the account and contract code changes,
+    /// they are stored in `account_changes` and `contract_deployments`
+    pub fn from_state_change_with_cause(
+        state_change_with_cause: &near_indexer_primitives::views::StateChangeWithCauseView,
+        block_header: &near_indexer_primitives::views::BlockHeaderView,
+        chunk_index_in_block: i32,
+        index_in_chunk: i32,
+    ) -> Option<Self> {
+        let near_indexer_primitives::views::StateChangeWithCauseView { cause, value } =
+            state_change_with_cause;
+
+        // For the access keys, the key is the public key and the value is JSON of the access key
+        let (account_id, change_kind, key, value) = match value {
+            near_indexer_primitives::views::StateChangeValueView::DataUpdate {
+                account_id,
+                key,
+                value,
+            } => (
+                account_id,
+                "DATA_UPDATE",
+                to_bytes(key),
+                Some(to_bytes(value)),
+            ),
+            near_indexer_primitives::views::StateChangeValueView::DataDeletion {
+                account_id,
+                key,
+            } => (account_id, "DATA_DELETION", to_bytes(key), None),
+            near_indexer_primitives::views::StateChangeValueView::AccessKeyUpdate {
+                account_id,
+                public_key,
+                access_key,
+            } => (
+                account_id,
+                "ACCESS_KEY_UPDATE",
+                public_key.to_string().into_bytes(),
+                Some(
+                    serde_json::to_vec(access_key)
+                        .expect("`AccessKeyView` expected to be serializable"),
+                ),
+            ),
+            near_indexer_primitives::views::StateChangeValueView::AccessKeyDeletion {
+                account_id,
+                public_key,
+            } => (
+                account_id,
+                "ACCESS_KEY_DELETION",
+                public_key.to_string().into_bytes(),
+                None,
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            account_id: account_id.to_string(),
+            change_kind: change_kind.to_string(),
+            key,
+            value,
+            caused_by_transaction_hash: if let near_indexer_primitives::views::StateChangeCauseView::TransactionProcessing { tx_hash } = cause {
+                Some(tx_hash.to_string())
+            } else {
+                None
+            },
+            caused_by_receipt_id: match cause {
+                near_indexer_primitives::views::StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash } => Some(receipt_hash.to_string()),
+                near_indexer_primitives::views::StateChangeCauseView::ActionReceiptGasReward { receipt_hash } => Some(receipt_hash.to_string()),
+                near_indexer_primitives::views::StateChangeCauseView::ReceiptProcessing { receipt_hash } => Some(receipt_hash.to_string()),
+                near_indexer_primitives::views::StateChangeCauseView::PostponedReceipt { receipt_hash } => Some(receipt_hash.to_string()),
+                _ => None,
+            },
+            update_reason: cause.print().to_string(),
+            block_height: block_header.height.into(),
+            block_timestamp: block_header.timestamp.into(),
+            chunk_index_in_block,
+            index_in_chunk,
+        })
+    }
+}
+
+fn to_bytes(value: &impl AsRef<[u8]>) -> Vec<u8> {
+    value.as_ref().to_vec()
+}
+
+impl crate::models::SqlMethods for ContractStateChange {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.account_id);
+        args.add(&self.change_kind);
+        args.add(&self.key);
+        args.add(&self.value);
+        args.add(&self.caused_by_transaction_hash);
+        args.add(&self.caused_by_receipt_id);
+        args.add(&self.update_reason);
+        args.add(&self.block_height);
+        args.add(&self.block_timestamp);
+        args.add(&self.chunk_index_in_block);
+        args.add(&self.index_in_chunk);
+    }
+
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO contract_state_changes VALUES ".to_owned()
+            + &crate::models::create_placeholders(items_count, ContractStateChange::field_count())?
+            + " ON CONFLICT DO NOTHING")
+    }
+
+    fn delete_query() -> String {
+        "DELETE FROM contract_state_changes WHERE block_timestamp >= $1".to_string()
+    }
+
+    fn name() -> String {
+        "contract_state_changes".to_string()
+    }
+}
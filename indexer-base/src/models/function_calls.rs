@@ -0,0 +1,129 @@
+use bigdecimal::BigDecimal;
+use sqlx::Arguments;
+
+use near_lake_framework::near_indexer_primitives;
+
+use crate::models::FieldCount;
+
+/// How the arguments larger than `max_size` bytes are stored
+#[derive(Debug, Clone, Copy)]
+pub struct ArgsLimit {
+    pub max_size: usize,
+    pub large_args_mode: indexer_opts::LargeArgsMode,
+}
+
+/// The normalized copy of `FUNCTION_CALL` actions from `action_receipts__actions`,
+/// `index_in_chunk` matches the index of the action there
+#[derive(Debug, sqlx::FromRow, FieldCount)]
+pub struct FunctionCall {
+    pub block_timestamp: BigDecimal,
+    pub receipt_id: String,
+    pub predecessor_account_id: String,
+    pub receiver_account_id: String,
+    pub method_name: String,
+    pub deposit: BigDecimal,
+    pub gas: BigDecimal,
+    pub args_size: i32,
+    // NULL if the arguments are not JSON or too large
+    pub args_json: Option<serde_json::Value>,
+    // Filled if the arguments are not JSON or too large (with `truncate` mode), NULL otherwise
+    pub args_base64: Option<String>,
+    pub args_truncated: bool,
+    pub chunk_index_in_block: i32,
+    pub index_in_chunk: i32,
+}
+
+impl FunctionCall {
+    pub fn try_from_action_view(
+        action_view: &near_indexer_primitives::views::ActionView,
+        receipt: &near_indexer_primitives::views::ReceiptView,
+        block_timestamp: u64,
+        chunk_index_in_block: i32,
+        index_in_chunk: i32,
+        args_limit: ArgsLimit,
+    ) -> anyhow::Result<Option<Self>> {
+        let (method_name, args, gas, deposit) = match action_view {
+            near_indexer_primitives::views::ActionView::FunctionCall {
+                method_name,
+                args,
+                gas,
+                deposit,
+            } => (method_name, args, gas, deposit),
+            _ => return Ok(None),
+        };
+        let decoded_args = base64::decode(args).map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to decode the arguments of receipt {} as base64: {}",
+                receipt.receipt_id,
+                err
+            )
+        })?;
+
+        let (args_json, args_base64, args_truncated) = if decoded_args.len() > args_limit.max_size {
+            match args_limit.large_args_mode {
+                indexer_opts::LargeArgsMode::Truncate => (
+                    None,
+                    Some(base64::encode(&decoded_args[..args_limit.max_size])),
+                    true,
+                ),
+                indexer_opts::LargeArgsMode::Omit => (None, None, true),
+            }
+        } else {
+            match serde_json::from_slice::<serde_json::Value>(&decoded_args) {
+                Ok(mut args_json) => {
+                    crate::models::serializers::escape_json(&mut args_json);
+                    (Some(args_json), None, false)
+                }
+                Err(_) => (None, Some(args.to_string()), false),
+            }
+        };
+
+        Ok(Some(Self {
+            block_timestamp: block_timestamp.into(),
+            receipt_id: receipt.receipt_id.to_string(),
+            predecessor_account_id: receipt.predecessor_id.to_string(),
+            receiver_account_id: receipt.receiver_id.to_string(),
+            method_name: method_name.escape_default().to_string(),
+            deposit: crate::models::u128_to_bigdecimal(*deposit),
+            gas: (*gas).into(),
+            args_size: decoded_args.len() as i32,
+            args_json,
+            args_base64,
+            args_truncated,
+            chunk_index_in_block,
+            index_in_chunk,
+        }))
+    }
+}
+
+impl crate::models::SqlMethods for FunctionCall {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.block_timestamp);
+        args.add(&self.receipt_id);
+        args.add(&self.predecessor_account_id);
+        args.add(&self.receiver_account_id);
+        args.add(&self.method_name);
+        args.add(&self.deposit);
+        args.add(&self.gas);
+        args.add(&self.args_size);
+        args.add(&self.args_json);
+        args.add(&self.args_base64);
+        args.add(&self.args_truncated);
+        args.add(&self.chunk_index_in_block);
+        args.add(&self.index_in_chunk);
+    }
+
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("INSERT INTO function_calls VALUES ".to_owned()
+            + &crate::models::create_placeholders(items_count, FunctionCall::field_count())?
+            + " ON CONFLICT DO NOTHING")
+    }
+
+    fn delete_query() -> String {
+        "DELETE FROM function_calls WHERE block_timestamp >= $1".to_string()
+    }
+
+    fn name() -> String {
+        "function_calls".to_string()
+    }
+}
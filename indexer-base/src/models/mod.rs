@@ -1,6 +1,8 @@
+use bigdecimal::BigDecimal;
 use futures::future::try_join_all;
 use sqlx::Arguments;
 use std::fmt::Write;
+use std::str::FromStr;
 
 use near_lake_framework::near_indexer_primitives::views::{
     AccessKeyPermissionView, ExecutionStatusView, StateChangeCauseView,
@@ -9,19 +11,30 @@ use near_lake_framework::near_indexer_primitives::views::{
 pub(crate) use account_changes::AccountChange;
 pub(crate) use blocks::Block;
 pub(crate) use chunks::Chunk;
+pub(crate) use contract_deployments::ContractDeployment;
+pub(crate) use contract_state_changes::ContractStateChange;
 pub(crate) use execution_outcomes::{ExecutionOutcome, ExecutionOutcomeReceipt};
+pub(crate) use function_calls::FunctionCall;
 pub(crate) use indexer_base::FieldCount;
 pub(crate) use receipts::{ActionReceipt, ActionReceiptAction, ActionReceiptsOutput, DataReceipt};
-pub(crate) use transactions::Transaction;
+pub(crate) use transactions::{Transaction, TransactionFinalStatus};
 
 pub(crate) mod account_changes;
 pub(crate) mod blocks;
 pub(crate) mod chunks;
+pub(crate) mod contract_deployments;
+pub(crate) mod contract_state_changes;
 pub(crate) mod execution_outcomes;
+pub(crate) mod function_calls;
 pub(crate) mod receipts;
 pub(crate) mod serializers;
 pub(crate) mod transactions;
 
+/// `BigDecimal` has no conversion from u128, so the balances go through the string
+pub(crate) fn u128_to_bigdecimal(value: u128) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).expect("u128 expected to be converted to BigDecimal")
+}
+
 pub trait FieldCount {
     /// Get the number of fields on a struct.
     fn field_count() -> usize;
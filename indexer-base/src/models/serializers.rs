@@ -137,7 +137,7 @@ pub(crate) fn extract_action_type_and_value_from_action_view(
 /// We can not store data with null-bytes in TEXT or JSONB fields
 /// of PostgreSQL
 /// ref: https://www.commandprompt.com/blog/null-characters-workarounds-arent-good-enough/
-fn escape_json(object: &mut serde_json::Value) {
+pub(crate) fn escape_json(object: &mut serde_json::Value) {
     match object {
         serde_json::Value::Object(ref mut value) => {
             for (_key, val) in value {
@@ -104,3 +104,51 @@ impl crate::models::SqlMethods for Transaction {
         "transactions".to_string()
     }
 }
+
+/// The status of the transaction computed after all its Receipts are executed.
+/// Unlike the other models, it updates the existing `transactions` rows
+#[derive(Debug, FieldCount)]
+pub struct TransactionFinalStatus {
+    pub transaction_hash: String,
+    pub final_status: String,
+    pub total_gas_burnt: BigDecimal,
+    pub total_tokens_burnt: BigDecimal,
+    pub completed_at_block_height: BigDecimal,
+}
+
+impl crate::models::SqlMethods for TransactionFinalStatus {
+    fn add_to_args(&self, args: &mut sqlx::postgres::PgArguments) {
+        args.add(&self.transaction_hash);
+        args.add(&self.final_status);
+        args.add(&self.total_gas_burnt);
+        args.add(&self.total_tokens_burnt);
+        args.add(&self.completed_at_block_height);
+    }
+
+    fn insert_query(items_count: usize) -> anyhow::Result<String> {
+        Ok("UPDATE transactions
+            SET final_status = statuses.final_status,
+                total_gas_burnt = statuses.total_gas_burnt,
+                total_tokens_burnt = statuses.total_tokens_burnt,
+                completed_at_block_height = statuses.completed_at_block_height
+            FROM (VALUES "
+            .to_owned()
+            + &crate::models::create_placeholders(
+                items_count,
+                TransactionFinalStatus::field_count(),
+            )?
+            + ") AS statuses (transaction_hash, final_status, total_gas_burnt, total_tokens_burnt, completed_at_block_height)
+            WHERE transactions.transaction_hash = statuses.transaction_hash")
+    }
+
+    fn delete_query() -> String {
+        "UPDATE transactions
+         SET final_status = NULL, total_gas_burnt = NULL, total_tokens_burnt = NULL, completed_at_block_height = NULL
+         WHERE completed_at_block_height >= $1"
+            .to_string()
+    }
+
+    fn name() -> String {
+        "transactions final statuses".to_string()
+    }
+}
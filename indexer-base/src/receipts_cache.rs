@@ -0,0 +1,289 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cached::{Cached, SizedCache};
+use tokio::sync::Mutex;
+
+use crate::{ParentTransactionHashString, ReceiptOrDataId, LOGGING_PREFIX};
+
+const RECEIPTS_TREE: &str = "receipts";
+const EXPIRATIONS_TREE: &str = "expirations";
+// The expiration timestamp followed by the encoded key, see [expiration_key]
+const EXPIRATION_KEY_LEN: usize = 8 + 33;
+const PRUNING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Maps Receipt IDs and Data IDs to their parent Transaction hashes.
+/// The hot entries live in memory, all the entries are also written to the optional on-disk store,
+/// so the mapping survives restarts and the database lookups become a rare fallback.
+/// The memory part is locked only for the short in-memory operations,
+/// the blocking IO of the store runs on the blocking thread pool without holding the lock.
+pub struct ReceiptsIndex {
+    cache: Mutex<SizedCache<ReceiptOrDataId, ParentTransactionHashString>>,
+    capacity: usize,
+    store: Option<ReceiptsStore>,
+}
+
+impl ReceiptsIndex {
+    pub fn new(size: usize, store: Option<ReceiptsStore>) -> Self {
+        Self {
+            cache: Mutex::new(SizedCache::with_size(size)),
+            capacity: size,
+            store,
+        }
+    }
+
+    /// How many entries are kept in memory
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub async fn get(&self, key: &ReceiptOrDataId) -> Option<ParentTransactionHashString> {
+        if let Some(transaction_hash) = self.cache.lock().await.cache_get(key) {
+            return Some(transaction_hash.clone());
+        }
+        let store = self.store.clone()?;
+        let store_key = key.clone();
+        let transaction_hash = run_blocking(move || store.get(&store_key)).await?;
+        self.cache
+            .lock()
+            .await
+            .cache_set(key.clone(), transaction_hash.clone());
+        Some(transaction_hash)
+    }
+
+    pub async fn set_many(&self, pairs: Vec<(ReceiptOrDataId, ParentTransactionHashString)>) {
+        if pairs.is_empty() {
+            return;
+        }
+        {
+            let mut cache = self.cache.lock().await;
+            for (key, transaction_hash) in &pairs {
+                cache.cache_set(key.clone(), transaction_hash.clone());
+            }
+        }
+        if let Some(store) = self.store.clone() {
+            run_blocking(move || store.set_many(&pairs)).await;
+        }
+    }
+
+    pub async fn remove(&self, key: &ReceiptOrDataId) -> Option<ParentTransactionHashString> {
+        let from_cache = self.cache.lock().await.cache_remove(key);
+        let from_store = match self.store.clone() {
+            Some(store) => {
+                let store_key = key.clone();
+                run_blocking(move || store.remove(&store_key)).await
+            }
+            None => None,
+        };
+        from_cache.or(from_store)
+    }
+}
+
+/// Runs the store operation on the blocking thread pool.
+/// The store is a best-effort optimization, so the failed task is logged and treated as the empty result
+async fn run_blocking<T, F>(operation: F) -> T
+where
+    T: Default + Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(operation).await {
+        Ok(result) => result,
+        Err(err) => {
+            tracing::warn!(
+                target: LOGGING_PREFIX,
+                "Receipts store task failed\n{:#?}",
+                err
+            );
+            T::default()
+        }
+    }
+}
+
+/// On-disk part of [ReceiptsIndex] based on sled.
+/// Every entry has the expiration time, expired entries are removed by [ReceiptsStore::spawn_pruning]
+#[derive(Clone)]
+pub struct ReceiptsStore {
+    receipts: sled::Tree,
+    // Key is the expiration timestamp followed by the receipts key, it allows to prune by range
+    expirations: sled::Tree,
+    ttl: std::time::Duration,
+}
+
+impl ReceiptsStore {
+    pub fn open(path: &std::path::Path, ttl: std::time::Duration) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+        let store = Self {
+            receipts: db.open_tree(RECEIPTS_TREE)?,
+            expirations: db.open_tree(EXPIRATIONS_TREE)?,
+            ttl,
+        };
+        tracing::info!(
+            target: LOGGING_PREFIX,
+            "Receipts store is opened at {} with {} entries",
+            path.display(),
+            store.receipts.len()
+        );
+        Ok(store)
+    }
+
+    /// Removes the expired entries periodically in the background
+    pub fn spawn_pruning(&self) {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNING_INTERVAL);
+            loop {
+                interval.tick().await;
+                let store = store.clone();
+                match tokio::task::spawn_blocking(move || store.prune()).await {
+                    Ok(Ok(pruned)) => tracing::info!(
+                        target: LOGGING_PREFIX,
+                        "Pruned {} expired entries from receipts store",
+                        pruned
+                    ),
+                    Ok(Err(err)) => tracing::warn!(
+                        target: LOGGING_PREFIX,
+                        "Failed to prune receipts store\n{:#?}",
+                        err
+                    ),
+                    Err(err) => tracing::warn!(
+                        target: LOGGING_PREFIX,
+                        "Receipts store pruning task failed\n{:#?}",
+                        err
+                    ),
+                }
+            }
+        });
+    }
+
+    // The store is a best-effort optimization: on errors we log them and fall back to the database
+    fn get(&self, key: &ReceiptOrDataId) -> Option<ParentTransactionHashString> {
+        let result = self
+            .receipts
+            .get(encode_key(key))
+            .map_err(anyhow::Error::from)
+            .and_then(|value| value.map(|value| decode_value(&value)).transpose());
+        match result {
+            Ok(value) => value.map(|(_, transaction_hash)| transaction_hash),
+            Err(err) => {
+                tracing::warn!(
+                    target: LOGGING_PREFIX,
+                    "Failed to read receipts store\n{:#?}",
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn set_many(&self, pairs: &[(ReceiptOrDataId, ParentTransactionHashString)]) {
+        let expires_at = now_secs() + self.ttl.as_secs();
+        let mut receipts = sled::Batch::default();
+        let mut expirations = sled::Batch::default();
+        for (key, transaction_hash) in pairs {
+            let key = encode_key(key);
+            receipts.insert(&key, encode_value(expires_at, transaction_hash));
+            expirations.insert(expiration_key(expires_at, &key), Vec::<u8>::new());
+        }
+        let result = self
+            .receipts
+            .apply_batch(receipts)
+            .and_then(|_| self.expirations.apply_batch(expirations));
+        if let Err(err) = result {
+            tracing::warn!(
+                target: LOGGING_PREFIX,
+                "Failed to write receipts store\n{:#?}",
+                err
+            );
+        }
+    }
+
+    fn remove(&self, key: &ReceiptOrDataId) -> Option<ParentTransactionHashString> {
+        let key = encode_key(key);
+        let result = self
+            .receipts
+            .remove(key)
+            .map_err(anyhow::Error::from)
+            .and_then(|value| value.map(|value| decode_value(&value)).transpose());
+        match result {
+            Ok(value) => value.map(|(expires_at, transaction_hash)| {
+                let _ = self.expirations.remove(expiration_key(expires_at, &key));
+                transaction_hash
+            }),
+            Err(err) => {
+                tracing::warn!(
+                    target: LOGGING_PREFIX,
+                    "Failed to remove from receipts store\n{:#?}",
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn prune(&self) -> anyhow::Result<usize> {
+        let mut pruned = 0;
+        for expiration in self.expirations.range(..now_secs().to_be_bytes()) {
+            let (expiration_key, _) = expiration?;
+            if expiration_key.len() != EXPIRATION_KEY_LEN {
+                anyhow::bail!(
+                    "Receipts store expiration key must be {} bytes long, got {}",
+                    EXPIRATION_KEY_LEN,
+                    expiration_key.len()
+                );
+            }
+            let (expires_at, key) = expiration_key.split_at(8);
+            // The entry could be set again later with the new expiration time, we should keep it then
+            if let Some(value) = self.receipts.get(key)? {
+                if decode_value(&value)?.0.to_be_bytes() == expires_at {
+                    self.receipts.remove(key)?;
+                    pruned += 1;
+                }
+            }
+            self.expirations.remove(expiration_key)?;
+        }
+        Ok(pruned)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before UNIX epoch")
+        .as_secs()
+}
+
+fn encode_key(key: &ReceiptOrDataId) -> [u8; 33] {
+    let (prefix, hash) = match key {
+        ReceiptOrDataId::ReceiptId(hash) => (0u8, hash),
+        ReceiptOrDataId::DataId(hash) => (1u8, hash),
+    };
+    let mut result = [0u8; 33];
+    result[0] = prefix;
+    result[1..].copy_from_slice(hash.as_ref());
+    result
+}
+
+fn expiration_key(expires_at: u64, key: &[u8; 33]) -> Vec<u8> {
+    let mut result = expires_at.to_be_bytes().to_vec();
+    result.extend_from_slice(key);
+    result
+}
+
+fn encode_value(expires_at: u64, transaction_hash: &str) -> Vec<u8> {
+    let mut result = expires_at.to_be_bytes().to_vec();
+    result.extend_from_slice(transaction_hash.as_bytes());
+    result
+}
+
+fn decode_value(value: &[u8]) -> anyhow::Result<(u64, ParentTransactionHashString)> {
+    if value.len() < 8 {
+        anyhow::bail!(
+            "Receipts store value must start with 8 bytes of expiration time, got {} bytes",
+            value.len()
+        );
+    }
+    let (expires_at, transaction_hash) = value.split_at(8);
+    Ok((
+        u64::from_be_bytes(expires_at.try_into()?),
+        String::from_utf8(transaction_hash.to_vec())?,
+    ))
+}
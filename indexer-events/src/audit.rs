@@ -0,0 +1,50 @@
+use crate::models::{fungible_token_events::FungibleTokenEvent, nft_events::NftEvent, SqlMethods};
+
+/// Reports the holes in `fungible_token_events` and `nft_events` tables.
+/// The tables are sparse: most of the blocks have no events, so `blocks` table of `indexer-base`
+/// can't tell which blocks should have them. The expected block heights are always taken from NEAR Lake
+/// (the blocks which produce at least one event).
+pub(crate) async fn run(
+    opts: &indexer_opts::Opts,
+    args: &indexer_opts::audit::AuditArgs,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<()> {
+    if args.source == indexer_opts::audit::AuditSource::Blocks {
+        anyhow::bail!(
+            "`audit --source blocks` is not supported by indexer-events, the event tables are sparse. Use `lake` or `auto`"
+        );
+    }
+    let (from_block_height, to_block_height) = opts.audit_range(args, pool).await?;
+    let tables = [FungibleTokenEvent::name(), NftEvent::name()];
+    let mut reporters: Vec<_> = tables
+        .iter()
+        .map(|table| indexer_opts::audit::HoleReporter::new(&opts.indexer_id, table))
+        .collect();
+
+    for (window_start, window_end) in
+        indexer_opts::audit::audit_windows(from_block_height, to_block_height)
+    {
+        let mut expected = opts
+            .expected_block_heights_from_lake(
+                window_start,
+                window_end,
+                |streamer_message| async move {
+                    crate::db_adapters::events::collect_tables_with_events(
+                        &streamer_message,
+                        &opts.chain_id,
+                    )
+                    .await
+                },
+            )
+            .await?;
+        for (table, reporter) in tables.iter().zip(reporters.iter_mut()) {
+            let expected = expected.remove(table).unwrap_or_default();
+            let stored = indexer_opts::audit::stored_block_heights(pool, table, &expected).await?;
+            reporter.push_window(&expected, &stored)?;
+        }
+    }
+    for reporter in reporters {
+        reporter.finish()?;
+    }
+    Ok(())
+}
@@ -27,6 +27,14 @@ pub(crate) async fn store_ft(
     streamer_message: &near_indexer_primitives::StreamerMessage,
     chain_id: &indexer_opts::ChainId,
 ) -> anyhow::Result<()> {
+    let events = collect_ft(streamer_message, chain_id).await?;
+    models::chunked_insert(pool, &events).await
+}
+
+pub(crate) async fn collect_ft(
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+    chain_id: &indexer_opts::ChainId,
+) -> anyhow::Result<Vec<FungibleTokenEvent>> {
     let mut events: Vec<FungibleTokenEvent> = vec![];
 
     let events_futures = streamer_message
@@ -36,7 +44,7 @@ pub(crate) async fn store_ft(
     for events_by_shard in try_join_all(events_futures).await? {
         events.extend(events_by_shard);
     }
-    models::chunked_insert(pool, &events).await
+    Ok(events)
 }
 
 pub(crate) fn filter_zeros_and_enumerate_events(
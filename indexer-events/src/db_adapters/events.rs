@@ -1,5 +1,6 @@
 use crate::db_adapters::event_types;
 use crate::db_adapters::{coin, nft};
+use crate::models::{fungible_token_events::FungibleTokenEvent, nft_events::NftEvent, SqlMethods};
 use futures::try_join;
 use near_lake_framework::near_indexer_primitives;
 
@@ -15,6 +16,27 @@ pub(crate) async fn store_events(
     Ok(())
 }
 
+/// Returns the names of the tables which would get the rows from this block.
+/// Used by `audit` to find the expected block heights
+pub(crate) async fn collect_tables_with_events(
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+    chain_id: &indexer_opts::ChainId,
+) -> anyhow::Result<Vec<String>> {
+    let (ft_events, nft_events) = try_join!(
+        coin::collect_ft(streamer_message, chain_id),
+        nft::collect_nft(streamer_message),
+    )?;
+
+    let mut tables = vec![];
+    if !ft_events.is_empty() {
+        tables.push(FungibleTokenEvent::name());
+    }
+    if !nft_events.is_empty() {
+        tables.push(NftEvent::name());
+    }
+    Ok(tables)
+}
+
 pub(crate) fn extract_events(
     outcome: &near_indexer_primitives::IndexerExecutionOutcomeWithReceipt,
 ) -> Vec<event_types::NearEvent> {
@@ -13,6 +13,13 @@ pub(crate) async fn store_nft(
     pool: &sqlx::Pool<sqlx::Postgres>,
     streamer_message: &near_indexer_primitives::StreamerMessage,
 ) -> anyhow::Result<()> {
+    let nep171_events = collect_nft(streamer_message).await?;
+    models::chunked_insert(pool, &nep171_events).await
+}
+
+pub(crate) async fn collect_nft(
+    streamer_message: &near_indexer_primitives::StreamerMessage,
+) -> anyhow::Result<Vec<NftEvent>> {
     let mut nep171_events: Vec<NftEvent> = vec![];
     let nft_events_futures = streamer_message.shards.iter().map(|shard| {
         nep171_events::collect_nep171_events(
@@ -24,7 +31,7 @@ pub(crate) async fn store_nft(
     for events in try_join_all(nft_events_futures).await? {
         nep171_events.extend(events);
     }
-    models::chunked_insert(pool, &nep171_events).await
+    Ok(nep171_events)
 }
 
 // todo it could be one method both for ft and nft
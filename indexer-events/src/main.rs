@@ -1,8 +1,8 @@
 // TODO cleanup imports in all the files in the end
 use futures::StreamExt;
-use indexer_opts::Parser;
 use near_lake_framework::near_indexer_primitives;
 
+mod audit;
 mod configs;
 mod db_adapters;
 mod metrics;
@@ -25,11 +25,16 @@ pub struct AccountWithContract {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
-    let opts = indexer_opts::Opts::parse();
+    let opts = indexer_opts::Opts::parse_with_config(env!("CARGO_PKG_NAME"))?;
     configs::init_tracing(opts.debug)?;
 
     let pool = sqlx::PgPool::connect(&opts.database_url).await?;
-    let lake_config = opts.to_lake_config(&pool).await?;
+    if let Some(indexer_opts::Command::Audit(args)) = &opts.command {
+        return audit::run(&opts, args, &pool).await;
+    }
+    let lake_config = opts
+        .to_lake_config(&pool, env!("CARGO_PKG_VERSION"))
+        .await?;
     let (sender, stream) = near_lake_framework::streamer(lake_config);
     let end_block_height = opts.end_block_height.unwrap_or(u64::MAX);
 
@@ -0,0 +1,18 @@
+-- record_kind options:
+--      {
+--         'START',
+--         'CHECKPOINT'
+--      }
+-- start_mode is filled only for 'START' records
+-- blocks_per_second is filled only for 'CHECKPOINT' records
+CREATE TABLE __meta_progress
+(
+    indexer_id        text             NOT NULL,
+    record_kind       text             NOT NULL,
+    recorded_at       timestamptz      NOT NULL,
+    block_height      numeric(20, 0)   NOT NULL,
+    blocks_per_second double precision,
+    start_mode        text,
+    version           text             NOT NULL
+);
+CREATE INDEX CONCURRENTLY __meta_progress_indexer_id_recorded_at_idx ON __meta_progress (indexer_id, recorded_at);
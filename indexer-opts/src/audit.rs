@@ -0,0 +1,504 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::io::Write;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+use near_lake_framework::near_indexer_primitives;
+use sqlx::{Arguments, Row};
+
+use crate::LOGGING_PREFIX;
+
+/// The range is audited by windows of this size, so neither the DB nor the memory
+/// has to deal with the whole range at once
+pub const AUDIT_WINDOW_SIZE: u64 = 10_000;
+
+/// The expected block heights with their timestamps.
+/// The timestamps allow to look for the rows by `event_index`, which starts with the block timestamp
+pub type ExpectedBlocks = BTreeMap<u64, u64>;
+
+/// Arguments for the `audit` subcommand
+#[derive(clap::Args, Debug, Clone)]
+pub struct AuditArgs {
+    /// First block height to audit. Default: `start_block_height` from `__meta`
+    #[clap(long)]
+    pub from_block_height: Option<u64>,
+    /// Last block height to audit. Default: `last_processed_block_height` from `__meta`
+    #[clap(long)]
+    pub to_block_height: Option<u64>,
+    /// Where to take the list of expected block heights from
+    #[clap(long, arg_enum, default_value = "auto")]
+    pub source: AuditSource,
+}
+
+/// Represents the source of expected block heights for the audit
+/// - Auto
+///  uses `blocks` table if it exists in the database, NEAR Lake otherwise.
+///  The micro-indexers with the sparse tables (e.g. `indexer-events`) always use NEAR Lake
+/// - Blocks
+///  uses `blocks` and `chunks` tables of `indexer-base`
+/// - Lake
+///  streams the blocks from NEAR Lake and checks which of them should produce the data
+#[derive(clap::ArgEnum, Debug, Clone, PartialEq, Eq)]
+pub enum AuditSource {
+    Auto,
+    Blocks,
+    Lake,
+}
+
+/// The range of block heights which should be indexed again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hole {
+    pub start_block_height: u64,
+    pub end_block_height: u64,
+}
+
+impl crate::Opts {
+    /// Resolves the audited range, taking the missing bounds from `__meta`
+    pub async fn audit_range(
+        &self,
+        args: &AuditArgs,
+        pool: &sqlx::Pool<sqlx::Postgres>,
+    ) -> anyhow::Result<(u64, u64)> {
+        let from_block_height = match args.from_block_height {
+            Some(height) => height,
+            None => crate::fetch_start_block_height_from_db(&self.indexer_id, pool).await?,
+        };
+        let to_block_height = match args.to_block_height {
+            Some(height) => height,
+            None => {
+                crate::fetch_last_processed_block_height_from_db(&self.indexer_id, pool).await?
+            }
+        };
+        if from_block_height > to_block_height {
+            anyhow::bail!(
+                "Nothing to audit: from_block_height {} is greater than to_block_height {}",
+                from_block_height,
+                to_block_height
+            );
+        }
+        Ok((from_block_height, to_block_height))
+    }
+
+    /// Resolves `AuditSource::Auto` to the concrete source
+    pub async fn audit_source(
+        &self,
+        args: &AuditArgs,
+        pool: &sqlx::Pool<sqlx::Postgres>,
+    ) -> anyhow::Result<AuditSource> {
+        Ok(match args.source {
+            AuditSource::Auto => {
+                let blocks_exist: bool =
+                    sqlx::query("SELECT to_regclass('public.blocks') IS NOT NULL")
+                        .fetch_one(pool)
+                        .await?
+                        .get(0);
+                if blocks_exist {
+                    AuditSource::Blocks
+                } else {
+                    AuditSource::Lake
+                }
+            }
+            ref source => source.clone(),
+        })
+    }
+
+    /// Streams the blocks from NEAR Lake in the given range and collects the heights
+    /// for each table returned by `tables_with_rows`.
+    /// Should be called for one window of [audit_windows], the heights are kept in memory
+    pub async fn expected_block_heights_from_lake<F, Fut>(
+        &self,
+        from_block_height: u64,
+        to_block_height: u64,
+        tables_with_rows: F,
+    ) -> anyhow::Result<BTreeMap<String, ExpectedBlocks>>
+    where
+        F: Fn(near_indexer_primitives::StreamerMessage) -> Fut,
+        Fut: Future<Output = anyhow::Result<Vec<String>>>,
+    {
+        let lake_config = match self.chain_id {
+            crate::ChainId::Mainnet => near_lake_framework::LakeConfigBuilder::default().mainnet(),
+            crate::ChainId::Testnet => near_lake_framework::LakeConfigBuilder::default().testnet(),
+        }
+        .start_block_height(from_block_height)
+        .build()?;
+        let (sender, mut stream) = near_lake_framework::streamer(lake_config);
+
+        let mut expected_block_heights: BTreeMap<String, ExpectedBlocks> = BTreeMap::new();
+        while let Some(streamer_message) = stream.recv().await {
+            let block_height = streamer_message.block.header.height;
+            let block_timestamp = streamer_message.block.header.timestamp;
+            if block_height > to_block_height {
+                break;
+            }
+            for table in tables_with_rows(streamer_message).await? {
+                expected_block_heights
+                    .entry(table)
+                    .or_default()
+                    .insert(block_height, block_timestamp);
+            }
+        }
+        drop(stream); // close the channel so the sender will stop
+        sender.abort();
+        Ok(expected_block_heights)
+    }
+}
+
+/// Splits the audited range into the windows of [AUDIT_WINDOW_SIZE] blocks, bounds are inclusive
+pub fn audit_windows(
+    from_block_height: u64,
+    to_block_height: u64,
+) -> impl Iterator<Item = (u64, u64)> {
+    (from_block_height..=to_block_height)
+        .step_by(AUDIT_WINDOW_SIZE as usize)
+        .map(move |window_start| {
+            let window_end = window_start
+                .saturating_add(AUDIT_WINDOW_SIZE - 1)
+                .min(to_block_height);
+            tracing::info!(
+                target: LOGGING_PREFIX,
+                "Auditing blocks {}..={} of {}",
+                window_start,
+                window_end,
+                to_block_height
+            );
+            (window_start, window_end)
+        })
+}
+
+/// Collects the heights of the blocks from `indexer-base` which have at least one chunk with used gas.
+/// It's the approximation: such block may produce the data, while the block without any work can't.
+pub async fn expected_block_heights_from_blocks(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    from_block_height: u64,
+    to_block_height: u64,
+) -> anyhow::Result<ExpectedBlocks> {
+    let rows = sqlx::query_with(
+        r#"
+SELECT DISTINCT blocks.block_height, blocks.block_timestamp
+FROM blocks JOIN chunks ON chunks.block_hash = blocks.block_hash
+WHERE chunks.gas_used > 0 AND blocks.block_height >= $1 AND blocks.block_height <= $2
+        "#,
+        range_args(from_block_height, to_block_height)?,
+    )
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok((
+                to_u64(row.get(0), "block_height")?,
+                to_u64(row.get(1), "block_timestamp")?,
+            ))
+        })
+        .collect()
+}
+
+/// Collects distinct `block_height` values of the expected blocks stored in the given table.
+/// The table should have `event_index` starting with the block timestamp (the partitioning key),
+/// so the lookup is bounded by the timestamps of the expected blocks
+pub async fn stored_block_heights(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    table: &str,
+    expected: &ExpectedBlocks,
+) -> anyhow::Result<BTreeSet<u64>> {
+    let (first, last) = match (expected.iter().next(), expected.iter().next_back()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Ok(BTreeSet::new()),
+    };
+    let mut args = range_args(*first.0, *last.0)?;
+    args.add(BigDecimal::from_str(
+        &(u128::from(*first.1) * EVENT_INDEX_BLOCK_MULTIPLIER).to_string(),
+    )?);
+    args.add(BigDecimal::from_str(
+        &((u128::from(*last.1) + 1) * EVENT_INDEX_BLOCK_MULTIPLIER).to_string(),
+    )?);
+    select_block_heights(
+        pool,
+        &format!(
+            r#"
+SELECT DISTINCT block_height FROM {}
+WHERE event_index >= $3 AND event_index < $4 AND block_height >= $1 AND block_height <= $2
+            "#,
+            table
+        ),
+        args,
+    )
+    .await
+}
+
+/// Finds the holes in `blocks` table of `indexer-base` and prints them, see [HoleReporter].
+/// Block heights are not contiguous on NEAR, so we rely on `prev_block_hash`:
+/// the hole is between the block which parent is not stored and the previous stored block.
+pub async fn report_holes_in_blocks(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    indexer_id: &str,
+    from_block_height: u64,
+    to_block_height: u64,
+) -> anyhow::Result<()> {
+    let mut reporter = HoleReporter::new(indexer_id, "blocks");
+    let mut last_stored: Option<u64> = None;
+    for (window_start, window_end) in audit_windows(from_block_height, to_block_height) {
+        let stored = select_block_heights(
+            pool,
+            "SELECT block_height FROM blocks WHERE block_height >= $1 AND block_height <= $2",
+            range_args(window_start, window_end)?,
+        )
+        .await?;
+        let orphans = select_block_heights(
+            pool,
+            r#"
+SELECT child.block_height
+FROM blocks child LEFT JOIN blocks parent ON parent.block_hash = child.prev_block_hash
+WHERE parent.block_hash IS NULL AND child.block_height >= $1 AND child.block_height <= $2
+            "#,
+            range_args(window_start, window_end)?,
+        )
+        .await?;
+
+        for block_height in stored {
+            match last_stored {
+                // The first stored block in the range, everything before it is missing
+                // unless its parent is stored: the heights are not contiguous
+                None if block_height > from_block_height && orphans.contains(&block_height) => {
+                    reporter.push_hole(Hole {
+                        start_block_height: from_block_height,
+                        end_block_height: block_height - 1,
+                    })?
+                }
+                Some(prev_stored) if orphans.contains(&block_height) => {
+                    reporter.push_hole(Hole {
+                        start_block_height: prev_stored + 1,
+                        end_block_height: block_height - 1,
+                    })?
+                }
+                _ => {}
+            }
+            last_stored = Some(block_height);
+        }
+    }
+    match last_stored {
+        None => reporter.push_hole(Hole {
+            start_block_height: from_block_height,
+            end_block_height: to_block_height,
+        })?,
+        Some(last_stored) if last_stored < to_block_height => reporter.push_hole(Hole {
+            start_block_height: last_stored + 1,
+            end_block_height: to_block_height,
+        })?,
+        _ => {}
+    }
+    reporter.finish()?;
+    Ok(())
+}
+
+/// Groups the expected but not stored heights into the holes and prints them to stdout
+/// as soon as they are closed, one JSON object per line, so the output could be fed to the backfill.
+/// Neighbouring expected heights are merged into one hole, even if they are not consecutive numbers
+/// or belong to the different windows.
+pub struct HoleReporter<W: std::io::Write = std::io::Stdout> {
+    indexer_id: String,
+    table: String,
+    current: Option<Hole>,
+    holes_count: usize,
+    out: W,
+}
+
+impl HoleReporter {
+    pub fn new(indexer_id: &str, table: &str) -> Self {
+        Self::with_writer(indexer_id, table, std::io::stdout())
+    }
+}
+
+impl<W: std::io::Write> HoleReporter<W> {
+    /// Prints the holes to `out` instead of stdout
+    pub fn with_writer(indexer_id: &str, table: &str, out: W) -> Self {
+        Self {
+            indexer_id: indexer_id.to_string(),
+            table: table.to_string(),
+            current: None,
+            holes_count: 0,
+            out,
+        }
+    }
+
+    /// Takes the expected and stored heights of the next window
+    pub fn push_window(
+        &mut self,
+        expected: &ExpectedBlocks,
+        stored: &BTreeSet<u64>,
+    ) -> anyhow::Result<()> {
+        for block_height in expected.keys() {
+            if stored.contains(block_height) {
+                if let Some(hole) = self.current.take() {
+                    self.print(&hole)?;
+                }
+                continue;
+            }
+            match self.current.as_mut() {
+                Some(hole) => hole.end_block_height = *block_height,
+                None => {
+                    self.current = Some(Hole {
+                        start_block_height: *block_height,
+                        end_block_height: *block_height,
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports the already computed hole
+    pub fn push_hole(&mut self, hole: Hole) -> anyhow::Result<()> {
+        if let Some(current) = self.current.take() {
+            self.print(&current)?;
+        }
+        self.print(&hole)
+    }
+
+    /// Prints the last hole and the summary, gives back the writer
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        if let Some(hole) = self.current.take() {
+            self.print(&hole)?;
+        }
+        tracing::info!(
+            target: LOGGING_PREFIX,
+            "Found {} hole(s) in {} for INDEXER ID {}",
+            self.holes_count,
+            self.table,
+            self.indexer_id
+        );
+        Ok(self.out)
+    }
+
+    fn print(&mut self, hole: &Hole) -> anyhow::Result<()> {
+        self.holes_count += 1;
+        writeln!(
+            self.out,
+            "{}",
+            serde_json::json!({
+                "indexer_id": self.indexer_id,
+                "table": self.table,
+                "start_block_height": hole.start_block_height,
+                "end_block_height": hole.end_block_height,
+            })
+        )?;
+        Ok(())
+    }
+}
+
+/// `event_index` is `block_timestamp * EVENT_INDEX_BLOCK_MULTIPLIER + <index inside the block>`
+const EVENT_INDEX_BLOCK_MULTIPLIER: u128 = 100_000_000 * 100_000_000;
+
+fn range_args(
+    from_block_height: u64,
+    to_block_height: u64,
+) -> anyhow::Result<sqlx::postgres::PgArguments> {
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(
+        BigDecimal::from_u64(from_block_height)
+            .ok_or_else(|| anyhow::anyhow!("Failed to convert `from_block_height` to u64"))?,
+    );
+    args.add(
+        BigDecimal::from_u64(to_block_height)
+            .ok_or_else(|| anyhow::anyhow!("Failed to convert `to_block_height` to u64"))?,
+    );
+    Ok(args)
+}
+
+fn to_u64(value: BigDecimal, name: &str) -> anyhow::Result<u64> {
+    value
+        .to_u64()
+        .ok_or_else(|| anyhow::anyhow!("Failed to convert `{}` to u64", name))
+}
+
+async fn select_block_heights(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    query: &str,
+    args: sqlx::postgres::PgArguments,
+) -> anyhow::Result<BTreeSet<u64>> {
+    sqlx::query_with(query, args)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| to_u64(row.get(0), "block_height"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected(heights: &[u64]) -> ExpectedBlocks {
+        heights
+            .iter()
+            .map(|height| (*height, *height * 1_000))
+            .collect()
+    }
+
+    fn stored(heights: &[u64]) -> BTreeSet<u64> {
+        heights.iter().copied().collect()
+    }
+
+    fn printed_holes(reporter: HoleReporter<Vec<u8>>) -> Vec<(u64, u64)> {
+        let out = reporter.finish().unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let hole: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert_eq!(hole["indexer_id"], "indexer");
+                assert_eq!(hole["table"], "events");
+                (
+                    hole["start_block_height"].as_u64().unwrap(),
+                    hole["end_block_height"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hole_continues_into_the_next_window() {
+        let mut reporter = HoleReporter::with_writer("indexer", "events", vec![]);
+        reporter
+            .push_window(&expected(&[1, 2, 5, 8]), &stored(&[1]))
+            .unwrap();
+        reporter
+            .push_window(&expected(&[10, 12, 15, 17]), &stored(&[15]))
+            .unwrap();
+        assert_eq!(printed_holes(reporter), vec![(2, 12), (17, 17)]);
+    }
+
+    #[test]
+    fn stored_height_closes_the_hole() {
+        let mut reporter = HoleReporter::with_writer("indexer", "events", vec![]);
+        reporter
+            .push_window(&expected(&[1, 2, 3, 4, 5, 6]), &stored(&[1, 3, 6]))
+            .unwrap();
+        assert_eq!(printed_holes(reporter), vec![(2, 2), (4, 5)]);
+    }
+
+    #[test]
+    fn computed_hole_closes_the_current_one() {
+        let mut reporter = HoleReporter::with_writer("indexer", "events", vec![]);
+        reporter
+            .push_window(&expected(&[1, 2]), &stored(&[1]))
+            .unwrap();
+        reporter
+            .push_hole(Hole {
+                start_block_height: 5,
+                end_block_height: 7,
+            })
+            .unwrap();
+        assert_eq!(printed_holes(reporter), vec![(2, 2), (5, 7)]);
+    }
+
+    #[test]
+    fn no_holes_prints_nothing() {
+        let mut reporter = HoleReporter::with_writer("indexer", "events", vec![]);
+        reporter
+            .push_window(&expected(&[1, 2, 3]), &stored(&[1, 2, 3]))
+            .unwrap();
+        reporter.push_window(&expected(&[]), &stored(&[])).unwrap();
+        assert!(printed_holes(reporter).is_empty());
+    }
+}
@@ -0,0 +1,439 @@
+use std::path::{Path, PathBuf};
+
+use clap::{ArgEnum, Parser};
+
+use crate::{BalanceMode, ChainId, LargeArgsMode, LockMode, Opts, StartMode};
+
+/// The settings which could be provided in the config file.
+/// Every key matches the name of CLI argument with underscores, e.g. `database_url`
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigSection {
+    debug: Option<bool>,
+    indexer_id: Option<String>,
+    indexer_type: Option<String>,
+    start_block_height: Option<u64>,
+    end_block_height: Option<u64>,
+    rpc_url: Option<String>,
+    chain_id: Option<ChainId>,
+    port: Option<u16>,
+    start_mode: Option<StartMode>,
+    database_url: Option<String>,
+    balance_mode: Option<BalanceMode>,
+    hybrid_check_every: Option<u64>,
+    rewind_blocks: Option<u64>,
+    receipts_store_path: Option<std::path::PathBuf>,
+    receipts_store_ttl_hours: Option<u64>,
+    receipts_cache_warm_up_blocks: Option<u64>,
+    allow_accounts: Option<Vec<String>>,
+    deny_accounts: Option<Vec<String>>,
+    watched_contracts: Option<Vec<String>>,
+    function_call_args_max_size: Option<usize>,
+    function_call_large_args: Option<LargeArgsMode>,
+    lock_mode: Option<LockMode>,
+}
+
+/// The config file: `[common]` section is applied to all the micro-indexers,
+/// the section named after the micro-indexer overrides it
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    common: ConfigSection,
+    #[serde(default, rename = "indexer-base")]
+    indexer_base: ConfigSection,
+    #[serde(default, rename = "indexer-accounts")]
+    indexer_accounts: ConfigSection,
+    #[serde(default, rename = "indexer-balances")]
+    indexer_balances: ConfigSection,
+    #[serde(default, rename = "indexer-events")]
+    indexer_events: ConfigSection,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("Failed to read config {}: {}", path.display(), err))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .map_err(|err| anyhow::anyhow!("Invalid config {}: {}", path.display(), err)),
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .map_err(|err| anyhow::anyhow!("Invalid config {}: {}", path.display(), err)),
+            _ => anyhow::bail!(
+                "Unsupported config format {}, expected .toml, .yaml or .yml",
+                path.display()
+            ),
+        }
+    }
+
+    fn section(&self, indexer_type: &str) -> anyhow::Result<&ConfigSection> {
+        Ok(match indexer_type {
+            "indexer-base" => &self.indexer_base,
+            "indexer-accounts" => &self.indexer_accounts,
+            "indexer-balances" => &self.indexer_balances,
+            "indexer-events" => &self.indexer_events,
+            _ => anyhow::bail!("Unknown config section [{}]", indexer_type),
+        })
+    }
+
+    /// Env variables to set for `indexer_type`: the section overrides `[common]`,
+    /// the variables for which `is_set` is true are left as the user set them
+    fn to_env(
+        &self,
+        indexer_type: &str,
+        is_set: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<Vec<(&'static str, String)>> {
+        let mut env: Vec<(&'static str, String)> = vec![];
+        for (name, value) in self
+            .section(indexer_type)?
+            .to_env()
+            .into_iter()
+            .chain(self.common.to_env())
+        {
+            if !is_set(name) && env.iter().all(|(added, _)| *added != name) {
+                env.push((name, value));
+            }
+        }
+        Ok(env)
+    }
+}
+
+impl ConfigSection {
+    /// Pairs of env variable name and value for all the keys set in the section
+    fn to_env(&self) -> Vec<(&'static str, String)> {
+        fn arg_enum<T: ArgEnum>(value: &T) -> String {
+            value
+                .to_possible_value()
+                .expect("ArgEnum variants are not skipped")
+                .get_name()
+                .to_string()
+        }
+
+        let mut env = vec![];
+        let mut push = |name: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                env.push((name, value));
+            }
+        };
+        push("INDEXER_DEBUG", self.debug.map(|value| value.to_string()));
+        push("INDEXER_ID", self.indexer_id.clone());
+        push("INDEXER_TYPE", self.indexer_type.clone());
+        push(
+            "START_BLOCK_HEIGHT",
+            self.start_block_height.map(|value| value.to_string()),
+        );
+        push(
+            "END_BLOCK_HEIGHT",
+            self.end_block_height.map(|value| value.to_string()),
+        );
+        push("RPC_URL", self.rpc_url.clone());
+        push("CHAIN_ID", self.chain_id.as_ref().map(arg_enum));
+        push("PORT", self.port.map(|value| value.to_string()));
+        push("START_MODE", self.start_mode.as_ref().map(arg_enum));
+        push("DATABASE_URL", self.database_url.clone());
+        push("BALANCE_MODE", self.balance_mode.as_ref().map(arg_enum));
+        push(
+            "HYBRID_CHECK_EVERY",
+            self.hybrid_check_every.map(|value| value.to_string()),
+        );
+        push(
+            "REWIND_BLOCKS",
+            self.rewind_blocks.map(|value| value.to_string()),
+        );
+        push(
+            "RECEIPTS_STORE_PATH",
+            self.receipts_store_path
+                .as_ref()
+                .map(|value| value.display().to_string()),
+        );
+        push(
+            "RECEIPTS_STORE_TTL_HOURS",
+            self.receipts_store_ttl_hours.map(|value| value.to_string()),
+        );
+        push(
+            "RECEIPTS_CACHE_WARM_UP_BLOCKS",
+            self.receipts_cache_warm_up_blocks
+                .map(|value| value.to_string()),
+        );
+        push(
+            "ALLOW_ACCOUNTS",
+            self.allow_accounts.as_ref().map(|value| value.join(",")),
+        );
+        push(
+            "DENY_ACCOUNTS",
+            self.deny_accounts.as_ref().map(|value| value.join(",")),
+        );
+        push(
+            "WATCHED_CONTRACTS",
+            self.watched_contracts.as_ref().map(|value| value.join(",")),
+        );
+        push(
+            "FUNCTION_CALL_ARGS_MAX_SIZE",
+            self.function_call_args_max_size
+                .map(|value| value.to_string()),
+        );
+        push(
+            "FUNCTION_CALL_LARGE_ARGS",
+            self.function_call_large_args.as_ref().map(arg_enum),
+        );
+        push("LOCK_MODE", self.lock_mode.as_ref().map(arg_enum));
+        env
+    }
+}
+
+impl Opts {
+    /// Parses [Opts] taking into account the config file given by `--config` (or `CONFIG` env var).
+    /// The precedence is: CLI arguments > env variables > `[<indexer_type>]` section > `[common]` section > defaults.
+    /// `indexer_type` is the name of the micro-indexer, e.g. `indexer-events`
+    pub fn parse_with_config(indexer_type: &str) -> anyhow::Result<Self> {
+        if let Some(path) = config_path() {
+            let config = ConfigFile::read(&path)?;
+            // The values from the file are passed through env, so clap resolves the precedence for us:
+            // the variable is set only if the user hasn't set it explicitly
+            for (name, value) in
+                config.to_env(indexer_type, |name| std::env::var_os(name).is_some())?
+            {
+                std::env::set_var(name, value);
+            }
+        }
+
+        let opts = Opts::parse();
+        match opts.command {
+            Some(crate::Command::ImportGenesis(_)) if indexer_type != "indexer-accounts" => {
+                anyhow::bail!("`import-genesis` is supported only by indexer-accounts")
+            }
+            Some(crate::Command::Reconcile(_)) if indexer_type != "indexer-accounts" => {
+                anyhow::bail!("`reconcile` is supported only by indexer-accounts")
+            }
+            Some(crate::Command::Reconcile(_)) if opts.rpc_url.is_none() => {
+                anyhow::bail!("`rpc_url` must be provided for `reconcile`")
+            }
+            _ => {}
+        }
+        opts.validate()?;
+        Ok(opts)
+    }
+
+    /// Checks the combinations of the settings which clap can't check by itself
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.command.is_some() {
+            // Subcommands read the state from the database and do not start the stream
+            return Ok(());
+        }
+        match self.start_mode {
+            StartMode::FromLatest if self.rpc_url.is_none() => {
+                anyhow::bail!("`rpc_url` must be provided for `start_mode` from-latest")
+            }
+            StartMode::FromInterruption
+            | StartMode::FromBlock
+            | StartMode::FromInterruptionOrBlock
+                if self.start_block_height.is_none() =>
+            {
+                anyhow::bail!(
+                    "`start_block_height` must be provided for `start_mode` {}",
+                    self.start_mode.as_str()
+                )
+            }
+            StartMode::FromBlock if !self.confirm_reset => {
+                anyhow::bail!("`confirm_reset` must be passed for `start_mode` from-block")
+            }
+            _ => {}
+        }
+        // The filtered out parents are never stored, so the database fallback can't resolve them
+        if (!self.allow_accounts.is_empty() || !self.deny_accounts.is_empty())
+            && self.receipts_store_path.is_none()
+        {
+            anyhow::bail!(
+                "`receipts_store_path` must be provided for `allow_accounts`/`deny_accounts`"
+            );
+        }
+        match self.balance_mode {
+            BalanceMode::RPC if self.rpc_url.is_none() => {
+                anyhow::bail!("`rpc_url` must be provided for `balance_mode` rpc")
+            }
+            BalanceMode::Hybrid if self.rpc_url.is_none() => {
+                anyhow::bail!("`rpc_url` must be provided for `balance_mode` hybrid")
+            }
+            _ => {}
+        }
+        if let (Some(start_block_height), Some(end_block_height)) =
+            (self.start_block_height, self.end_block_height)
+        {
+            if start_block_height > end_block_height {
+                anyhow::bail!(
+                    "`end_block_height` {} is less than `start_block_height` {}",
+                    end_block_height,
+                    start_block_height
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the config path before clap parses the arguments, since the file affects the parsing
+fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy().to_string();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("INDEXER_CONFIG").map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML_CONFIG: &str = r#"
+[common]
+chain_id = "mainnet"
+database_url = "postgres://common"
+rpc_url = "https://common.rpc"
+
+[indexer-events]
+rpc_url = "https://events.rpc"
+lock_mode = "standby"
+"#;
+
+    fn value<'a>(env: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+        env.iter()
+            .find(|(added, _)| *added == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse_opts(args: &[&str]) -> Opts {
+        let required = [
+            "indexer",
+            "--indexer-id",
+            "test",
+            "--indexer-type",
+            "test",
+            "--chain-id",
+            "testnet",
+            "--database-url",
+            "postgres://test",
+        ];
+        Opts::try_parse_from(required.iter().chain(args).copied()).unwrap()
+    }
+
+    #[test]
+    fn section_overrides_common() {
+        let config: ConfigFile = toml::from_str(TOML_CONFIG).unwrap();
+
+        let env = config.to_env("indexer-events", |_| false).unwrap();
+        assert_eq!(value(&env, "RPC_URL"), Some("https://events.rpc"));
+        assert_eq!(value(&env, "DATABASE_URL"), Some("postgres://common"));
+        assert_eq!(value(&env, "CHAIN_ID"), Some("mainnet"));
+        assert_eq!(value(&env, "LOCK_MODE"), Some("standby"));
+        assert_eq!(env.iter().filter(|(name, _)| *name == "RPC_URL").count(), 1);
+
+        let env = config.to_env("indexer-base", |_| false).unwrap();
+        assert_eq!(value(&env, "RPC_URL"), Some("https://common.rpc"));
+        assert_eq!(value(&env, "LOCK_MODE"), None);
+    }
+
+    #[test]
+    fn user_env_is_not_overwritten() {
+        let config: ConfigFile = toml::from_str(TOML_CONFIG).unwrap();
+        let env = config
+            .to_env("indexer-events", |name| {
+                name == "RPC_URL" || name == "DATABASE_URL"
+            })
+            .unwrap();
+        assert_eq!(value(&env, "RPC_URL"), None);
+        assert_eq!(value(&env, "DATABASE_URL"), None);
+        assert_eq!(value(&env, "CHAIN_ID"), Some("mainnet"));
+    }
+
+    #[test]
+    fn enums_and_lists_are_passed_as_cli_values() {
+        let config: ConfigFile = serde_yaml::from_str(
+            r#"
+indexer-base:
+  start_mode: from-interruption-or-block
+  balance_mode: hybrid
+  function_call_large_args: omit
+  lock_mode: fail-fast
+  chain_id: testnet
+  allow_accounts: ["token.sweat", "*.sweat"]
+  debug: true
+  start_block_height: 42
+"#,
+        )
+        .unwrap();
+        let env = config.to_env("indexer-base", |_| false).unwrap();
+        assert_eq!(
+            value(&env, "START_MODE"),
+            Some("from-interruption-or-block")
+        );
+        assert_eq!(value(&env, "BALANCE_MODE"), Some("hybrid"));
+        assert_eq!(value(&env, "FUNCTION_CALL_LARGE_ARGS"), Some("omit"));
+        assert_eq!(value(&env, "LOCK_MODE"), Some("fail-fast"));
+        assert_eq!(value(&env, "CHAIN_ID"), Some("testnet"));
+        assert_eq!(value(&env, "ALLOW_ACCOUNTS"), Some("token.sweat,*.sweat"));
+        assert_eq!(value(&env, "INDEXER_DEBUG"), Some("true"));
+        assert_eq!(value(&env, "START_BLOCK_HEIGHT"), Some("42"));
+    }
+
+    #[test]
+    fn unknown_keys_are_named_in_the_error() {
+        let err = toml::from_str::<ConfigFile>("[common]\ndatabase-url = \"postgres://test\"\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("database-url"), "{}", err);
+
+        let err = serde_yaml::from_str::<ConfigFile>("indexer-foo:\n  debug: true\n").unwrap_err();
+        assert!(err.to_string().contains("indexer-foo"), "{}", err);
+
+        let config = ConfigFile::default();
+        let err = config.to_env("indexer-foo", |_| false).unwrap_err();
+        assert!(err.to_string().contains("indexer-foo"), "{}", err);
+    }
+
+    #[test]
+    fn validate_checks_the_combinations() {
+        assert!(parse_opts(&["--start-block-height", "1"])
+            .validate()
+            .is_ok());
+        assert!(parse_opts(&["--start-mode", "from-latest"])
+            .validate()
+            .is_err());
+        assert!(
+            parse_opts(&["--start-mode", "from-latest", "--rpc-url", "https://rpc"])
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            parse_opts(&["--start-mode", "from-block", "--start-block-height", "1"])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            parse_opts(&["--start-block-height", "10", "--end-block-height", "5"])
+                .validate()
+                .is_err()
+        );
+        assert!(parse_opts(&[
+            "--start-block-height",
+            "1",
+            "--allow-accounts",
+            "token.sweat"
+        ])
+        .validate()
+        .is_err());
+        assert!(
+            parse_opts(&["--start-block-height", "1", "--balance-mode", "hybrid"])
+                .validate()
+                .is_err()
+        );
+        // Subcommands do not start the stream
+        assert!(parse_opts(&["--start-mode", "from-latest", "audit"])
+            .validate()
+            .is_ok());
+    }
+}
@@ -5,8 +5,15 @@ use near_jsonrpc_client::{methods, JsonRpcClient};
 use near_lake_framework::near_indexer_primitives::types::{BlockReference, Finality};
 use sqlx::{Arguments, Row};
 
+pub mod audit;
+mod config;
+
 pub(crate) const LOGGING_PREFIX: &str = "indexer";
 
+const LOCK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const LOCK_HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const PROGRESS_CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// NEAR Indexer Opts
 /// Start options for NEAR micro indexers
 #[derive(Parser, Debug)]
@@ -19,8 +26,13 @@ pub(crate) const LOGGING_PREFIX: &str = "indexer";
     next_line_help(true)
 )]
 pub struct Opts {
-    /// Enabled Indexer for Explorer debug level of logs
-    #[clap(long)]
+    /// Path to the TOML/YAML config file. CLI arguments and env variables take precedence over it.
+    /// The env variable is prefixed, since `CONFIG` is too generic
+    #[clap(long, env = "INDEXER_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
+    /// Enabled Indexer for Explorer debug level of logs.
+    /// The env variable is prefixed, since `DEBUG` is used by many other tools
+    #[clap(long, env = "INDEXER_DEBUG")]
     pub debug: bool,
     /// Sets the micro-indexer instance ID (for reading/writing indexer meta-data)
     #[clap(long, env)]
@@ -50,20 +62,148 @@ pub struct Opts {
     #[clap(long, short, env)]
     pub database_url: String,
     /// How NEAR balances, which are used to calculate deltas, should be fetched, either from JSON
-    /// RPC or from the database. This is only applicable for the `indexer-balances` micro-indexer
+    /// RPC, from the database, or from the database verified by JSON RPC.
+    /// This is only applicable for the `indexer-balances` micro-indexer
     #[clap(long, env, arg_enum, default_value = "db")]
     pub balance_mode: BalanceMode,
+    /// Every N-th balance lookup is verified against JSON RPC in `balance-mode hybrid`, 0 disables the sampling.
+    /// The first lookup of each account after the start is always verified
+    #[clap(long, env, default_value_t = 100)]
+    pub hybrid_check_every: u64,
+    /// How many blocks before `last_processed_block_height` to resume from on `start-mode from-interruption`
+    #[clap(long, env, default_value_t = 100)]
+    pub rewind_blocks: u64,
+    /// Confirms that `start-mode from-block` may reset `last_processed_block_height` of the existing `indexer-id`
+    #[clap(long)]
+    pub confirm_reset: bool,
+    /// Directory of the on-disk receipt-to-transaction store. In-memory cache only if not set.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env)]
+    pub receipts_store_path: Option<std::path::PathBuf>,
+    /// How long the entries of the receipt-to-transaction store are kept, in hours
+    #[clap(long, env, default_value_t = 24)]
+    pub receipts_store_ttl_hours: u64,
+    /// How many last indexed blocks to load into the receipt-to-transaction cache on start. 0 disables the warm-up.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env, default_value_t = 1000)]
+    pub receipts_cache_warm_up_blocks: u64,
+    /// Comma-separated accounts to index, exact (`token.sweat`) or by suffix (`*.sweat`). All accounts if empty.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env, value_delimiter = ',')]
+    pub allow_accounts: Vec<String>,
+    /// Comma-separated accounts to skip, exact or by suffix. Takes precedence over `allow-accounts`.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env, value_delimiter = ',')]
+    pub deny_accounts: Vec<String>,
+    /// Comma-separated contracts, exact or by suffix, which storage and access key changes are stored
+    /// to `contract_state_changes`. The table is not filled if empty.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env, value_delimiter = ',')]
+    pub watched_contracts: Vec<String>,
+    /// The maximum size of the decoded arguments stored to `function_calls`, in bytes.
+    /// This is only applicable for the `indexer-base` micro-indexer
+    #[clap(long, env, default_value_t = 16384)]
+    pub function_call_args_max_size: usize,
+    /// What to store to `function_calls` if the arguments are larger than `function-call-args-max-size`
+    #[clap(long, env, arg_enum, default_value = "truncate")]
+    pub function_call_large_args: LargeArgsMode,
+    /// What to do if another instance with the same `indexer-id` is already running
+    #[clap(long, env, arg_enum, default_value = "fail-fast")]
+    pub lock_mode: LockMode,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Represents the subcommands which could be run instead of indexing
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Reports the missing block heights for the indexer as JSON lines and exits
+    Audit(audit::AuditArgs),
+    /// Stores the accounts and access keys from the genesis records and exits.
+    /// This is only applicable for the `indexer-accounts` micro-indexer
+    ImportGenesis(ImportGenesisArgs),
+    /// Compares the sample of alive accounts and access keys with JSON RPC, stores the mismatches and exits.
+    /// This is only applicable for the `indexer-accounts` micro-indexer
+    Reconcile(ReconcileArgs),
+}
+
+/// Arguments for the `import-genesis` subcommand
+#[derive(clap::Args, Debug, Clone)]
+pub struct ImportGenesisArgs {
+    /// Path to `genesis.json` or to the records file (JSON array of the state records)
+    #[clap(long)]
+    pub genesis_path: std::path::PathBuf,
+    /// Genesis block height. Default: `genesis_height` from `genesis.json`, required for the records file
+    #[clap(long)]
+    pub genesis_height: Option<u64>,
+}
+
+/// Arguments for the `reconcile` subcommand
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReconcileArgs {
+    /// Block height to compare the state at, the RPC node should have it. Default: `last_processed_block_height` from `__meta`
+    #[clap(long)]
+    pub block_height: Option<u64>,
+    /// How many alive accounts to check
+    #[clap(long, default_value_t = 1000)]
+    pub sample_size: u32,
+    /// Fixes the mismatches in `accounts` and `access_keys` after storing them
+    #[clap(long)]
+    pub auto_fix: bool,
+}
+
+impl Opts {
+    /// Resolves the block height for `reconcile`, taking it from `__meta` if not given
+    pub async fn reconcile_block_height(
+        &self,
+        args: &ReconcileArgs,
+        pool: &sqlx::Pool<sqlx::Postgres>,
+    ) -> anyhow::Result<u64> {
+        match args.block_height {
+            Some(height) => Ok(height),
+            None => fetch_last_processed_block_height_from_db(&self.indexer_id, pool).await,
+        }
+    }
 }
 
 /// Represents the type of balance fetching mode
-#[derive(ArgEnum, Debug, Clone, PartialEq, Eq)]
+#[derive(ArgEnum, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum BalanceMode {
+    #[serde(rename = "db")]
     DB,
+    #[serde(rename = "rpc")]
     RPC,
+    #[serde(rename = "hybrid")]
+    Hybrid,
+}
+
+/// Represents how `indexer-base` stores the large function call arguments
+/// - Truncate
+///  stores the first `function-call-args-max-size` bytes as base64, without the decoded JSON
+/// - Omit
+///  stores only the size of the arguments
+#[derive(ArgEnum, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LargeArgsMode {
+    Truncate,
+    Omit,
+}
+
+/// Represents the behaviour when the `indexer-id` is already locked by another instance
+/// - FailFast
+///  will stop the instance with an error
+/// - Standby
+///  will wait until the lock is released (e.g. the leader dies) and then take over
+#[derive(ArgEnum, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockMode {
+    FailFast,
+    Standby,
 }
 
 /// Represents the chain-id variants for indexer to stream from
-#[derive(ArgEnum, Debug, Clone, PartialEq, Eq)]
+#[derive(ArgEnum, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum ChainId {
     Mainnet,
     Testnet,
@@ -76,14 +216,35 @@ pub enum ChainId {
 /// - FromInterruption
 ///  will register an indexer with the given `indexer-id` and `indexer-type` along with the provided
 ///  `start-block-height` and then will fetch the `last_processed_block_height` to continue the stream
-#[derive(ArgEnum, Debug, Clone)]
+/// - FromBlock
+///  will register an indexer with the given `indexer-id` and `indexer-type` and reset its
+///  `last_processed_block_height` to the provided `start-block-height`. Requires `--confirm-reset`
+/// - FromInterruptionOrBlock
+///  will continue the stream from `last_processed_block_height` if the indexer with the given `indexer-id`
+///  is already registered, or register it and start from the provided `start-block-height` otherwise
+#[derive(ArgEnum, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
 pub enum StartMode {
     FromLatest,
     FromInterruption,
+    FromBlock,
+    FromInterruptionOrBlock,
+}
+
+impl StartMode {
+    fn as_str(&self) -> &str {
+        match self {
+            StartMode::FromLatest => "from-latest",
+            StartMode::FromInterruption => "from-interruption",
+            StartMode::FromBlock => "from-block",
+            StartMode::FromInterruptionOrBlock => "from-interruption-or-block",
+        }
+    }
 }
 
 /// Helper function to perform an update in `__meta` table for the given `indexer-id`
 /// with the given `last_processed_block_height`
+/// Also appends a checkpoint to `__meta_progress` if the previous one is older than a minute
 /// Will throw an error in cases:
 /// - database error
 /// - conversion u64 to [bigdecimal::BigDecimal] error
@@ -99,7 +260,7 @@ pub async fn update_meta(
         };
 
     let mut args = sqlx::postgres::PgArguments::default();
-    args.add(block_height);
+    args.add(block_height.clone());
     args.add(indexer_id);
 
     match sqlx::query_with(
@@ -112,7 +273,7 @@ pub async fn update_meta(
     .execute(db_with_meta_data_pool)
     .await
     {
-        Ok(_) => Ok(()),
+        Ok(_) => record_checkpoint(db_with_meta_data_pool, indexer_id, block_height).await,
         Err(err) => {
             tracing::warn!(
                 target: LOGGING_PREFIX,
@@ -126,16 +287,44 @@ pub async fn update_meta(
 }
 
 impl Opts {
+    /// Takes the advisory lock of `indexer-id` according to `lock-mode`.
+    /// Everything that writes the data of the indexer (including the subcommands) should hold it,
+    /// [Opts::to_lake_config] takes it implicitly
+    pub async fn lock_indexer(
+        &self,
+        db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+    ) -> anyhow::Result<IndexerLock> {
+        lock_indexer(db_with_meta_data_pool, &self.indexer_id, &self.lock_mode).await
+    }
+
     /// returns a [near_lake_framework::LakeConfig] object where AWS credentials are sourced from
     /// .env file first, and then from .aws/credentials if not found.
     /// https://docs.aws.amazon.com/sdk-for-rust/latest/dg/credentials.html
+    /// `version` is the version of the running micro-indexer, it is stored in `__meta_progress`
     pub async fn to_lake_config(
         &self,
         db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+        version: &str,
     ) -> anyhow::Result<near_lake_framework::LakeConfig> {
+        self.to_lake_config_with_start_block_height(db_with_meta_data_pool, version)
+            .await
+            .map(|(lake_config, _)| lake_config)
+    }
+
+    /// Same as [Opts::to_lake_config], also returns the block height the stream starts from
+    pub async fn to_lake_config_with_start_block_height(
+        &self,
+        db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+        version: &str,
+    ) -> anyhow::Result<(near_lake_framework::LakeConfig, u64)> {
         let config_builder = near_lake_framework::LakeConfigBuilder::default();
         tracing::info!(target: LOGGING_PREFIX, "CHAIN_ID: {:?}", self.chain_id);
 
+        // The indexer keeps the lock until the process stops
+        self.lock_indexer(db_with_meta_data_pool)
+            .await?
+            .keep_until_exit();
+
         let start_block_height = match self.start_mode {
             StartMode::FromLatest => {
                 let start_block_height_from_rpc = fetch_latest_block_height_from_rpc(
@@ -166,7 +355,66 @@ impl Opts {
                 // Starting slightly before the interruption to be sure we haven't missed anything
                 fetch_last_processed_block_height_from_db(&self.indexer_id, db_with_meta_data_pool)
                     .await?
-                    .saturating_sub(100)
+                    .saturating_sub(self.rewind_blocks)
+            }
+            StartMode::FromBlock => {
+                let start_block_height = self
+                    .start_block_height
+                    .expect("`start-block-height` must be provided to use `start-mode from-block`");
+                if !self.confirm_reset {
+                    anyhow::bail!(
+                        "`start-mode from-block` resets the progress of INDEXER ID {}, pass `--confirm-reset` to proceed",
+                        self.indexer_id
+                    );
+                }
+                register_indexer(
+                    db_with_meta_data_pool,
+                    &self.indexer_id,
+                    &self.indexer_type,
+                    start_block_height,
+                    self.end_block_height,
+                )
+                .await?;
+                reset_last_processed_block_height(
+                    db_with_meta_data_pool,
+                    &self.indexer_id,
+                    start_block_height,
+                )
+                .await?;
+                tracing::warn!(
+                    target: LOGGING_PREFIX,
+                    "INDEXER ID {} progress is reset to block {}",
+                    self.indexer_id,
+                    start_block_height
+                );
+                start_block_height
+            }
+            StartMode::FromInterruptionOrBlock => {
+                match try_fetch_last_processed_block_height_from_db(
+                    &self.indexer_id,
+                    db_with_meta_data_pool,
+                )
+                .await?
+                {
+                    // Starting slightly before the interruption to be sure we haven't missed anything
+                    Some(last_processed_block_height) => {
+                        last_processed_block_height.saturating_sub(self.rewind_blocks)
+                    }
+                    None => {
+                        let start_block_height = self.start_block_height.expect(
+                            "`start-block-height` must be provided to use `start-mode from-interruption-or-block`",
+                        );
+                        register_indexer(
+                            db_with_meta_data_pool,
+                            &self.indexer_id,
+                            &self.indexer_type,
+                            start_block_height,
+                            self.end_block_height,
+                        )
+                        .await?;
+                        start_block_height
+                    }
+                }
             }
         };
 
@@ -175,19 +423,29 @@ impl Opts {
             "Indexer will start from block {}",
             start_block_height
         );
+        record_start(
+            db_with_meta_data_pool,
+            &self.indexer_id,
+            &self.start_mode,
+            start_block_height,
+            version,
+        )
+        .await?;
 
-        Ok(match self.chain_id {
+        let lake_config = match self.chain_id {
             ChainId::Mainnet => config_builder.mainnet(),
             ChainId::Testnet => config_builder.testnet(),
         }
         .start_block_height(start_block_height)
-        .build()?)
+        .build()?;
+        Ok((lake_config, start_block_height))
     }
 }
 
 /// Internal function to perform a registration of the indexer with the given `indexer-id` and `indexer-type`
 /// in the `__meta` table of the provided database.
-/// Will call [apply_migration] function in the beginning.
+/// [lock_indexer] must be called before, so only one instance per `indexer-id`
+/// could write the data at the same time.
 async fn register_indexer(
     db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
     indexer_id: &str,
@@ -228,6 +486,202 @@ ON CONFLICT (indexer_id) DO UPDATE
     Ok(())
 }
 
+/// Internal function to set `last_processed_block_height` of the given `indexer-id` to the given value,
+/// even if it goes backwards. Used by `start-mode from-block`
+async fn reset_last_processed_block_height(
+    db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+    indexer_id: &str,
+    block_height: u64,
+) -> anyhow::Result<()> {
+    let block_height = BigDecimal::from_u64(block_height)
+        .ok_or_else(|| anyhow::anyhow!("Failed to convert `start_block_height` to u64"))?;
+
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(block_height);
+    args.add(indexer_id);
+
+    sqlx::query_with(
+        r#"
+UPDATE __meta SET last_processed_block_height = $1 WHERE indexer_id = $2
+        "#,
+        args,
+    )
+    .execute(db_with_meta_data_pool)
+    .await?;
+    Ok(())
+}
+
+/// Internal function to append the record about the indexer start to `__meta_progress`.
+/// It allows to audit the restarts, e.g. when `from-latest` skipped some blocks.
+async fn record_start(
+    db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+    indexer_id: &str,
+    start_mode: &StartMode,
+    start_block_height: u64,
+    version: &str,
+) -> anyhow::Result<()> {
+    let start_block_height = BigDecimal::from_u64(start_block_height)
+        .ok_or_else(|| anyhow::anyhow!("Failed to convert `start_block_height` to u64"))?;
+
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(indexer_id);
+    args.add(start_block_height);
+    args.add(start_mode.as_str());
+    args.add(version);
+
+    sqlx::query_with(
+        r#"
+INSERT INTO __meta_progress (indexer_id, record_kind, recorded_at, block_height, blocks_per_second, start_mode, version)
+VALUES ($1, 'START', now(), $2, NULL, $3, $4)
+        "#,
+        args,
+    )
+    .execute(db_with_meta_data_pool)
+    .await?;
+    Ok(())
+}
+
+/// Internal function to append the periodic checkpoint to `__meta_progress`.
+/// Throughput is calculated against the previous record of the same `indexer-id`,
+/// the version is taken from the previous record as well.
+/// Does nothing if the previous record is newer than [PROGRESS_CHECKPOINT_INTERVAL].
+async fn record_checkpoint(
+    db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+    indexer_id: &str,
+    block_height: BigDecimal,
+) -> anyhow::Result<()> {
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(indexer_id);
+    args.add(block_height);
+    args.add(PROGRESS_CHECKPOINT_INTERVAL.as_secs_f64());
+
+    match sqlx::query_with(
+        r#"
+INSERT INTO __meta_progress (indexer_id, record_kind, recorded_at, block_height, blocks_per_second, start_mode, version)
+SELECT $1, 'CHECKPOINT', now(), $2,
+       (($2 - prev.block_height) / GREATEST(EXTRACT(EPOCH FROM now() - prev.recorded_at), 1))::double precision,
+       NULL, prev.version
+FROM (SELECT block_height, recorded_at, version
+      FROM __meta_progress
+      WHERE indexer_id = $1
+      ORDER BY recorded_at DESC
+      LIMIT 1) prev
+WHERE prev.recorded_at < now() - make_interval(secs => $3)
+        "#,
+        args,
+    )
+    .execute(db_with_meta_data_pool)
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            tracing::warn!(
+                target: LOGGING_PREFIX,
+                "Failed to record progress for INDEXER ID {}\n{:#?}",
+                indexer_id,
+                err,
+            );
+            anyhow::bail!(err)
+        }
+    }
+}
+
+/// The advisory lock of the `indexer-id`, see [Opts::lock_indexer].
+/// The lock is released when the guard is dropped
+pub struct IndexerLock {
+    holder: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl IndexerLock {
+    /// Keeps the lock until the process stops, even after the guard is dropped
+    pub fn keep_until_exit(mut self) {
+        self.holder.take();
+    }
+}
+
+impl Drop for IndexerLock {
+    fn drop(&mut self) {
+        // Aborting the holder closes its connection, so Postgres releases the lock
+        if let Some(holder) = self.holder.take() {
+            holder.abort();
+        }
+    }
+}
+
+/// Internal function to take a session-level advisory lock derived from the given `indexer-id`.
+/// The lock is held by a dedicated connection detached from the pool, it is released by Postgres
+/// as soon as the session ends, so the standby instance takes over automatically when the leader dies.
+/// If the lock connection is lost, the process is stopped to avoid concurrent writes.
+async fn lock_indexer(
+    db_with_meta_data_pool: &sqlx::Pool<sqlx::Postgres>,
+    indexer_id: &str,
+    lock_mode: &LockMode,
+) -> anyhow::Result<IndexerLock> {
+    let mut connection = db_with_meta_data_pool.acquire().await?.detach();
+
+    loop {
+        let mut args = sqlx::postgres::PgArguments::default();
+        args.add(indexer_id);
+        let is_locked: bool = sqlx::query_with(
+            r#"
+SELECT pg_try_advisory_lock(('x' || substr(md5($1), 1, 16))::bit(64)::bigint)
+            "#,
+            args,
+        )
+        .fetch_one(&mut connection)
+        .await?
+        .get(0);
+
+        if is_locked {
+            break;
+        }
+        match lock_mode {
+            LockMode::FailFast => anyhow::bail!(
+                "INDEXER ID {} is already locked by another instance",
+                indexer_id
+            ),
+            LockMode::Standby => {
+                tracing::info!(
+                    target: LOGGING_PREFIX,
+                    "INDEXER ID {} is locked by another instance, waiting as standby...",
+                    indexer_id
+                );
+                tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+            }
+        }
+    }
+
+    tracing::info!(
+        target: LOGGING_PREFIX,
+        "Lock for INDEXER ID {} is acquired",
+        indexer_id
+    );
+    Ok(IndexerLock {
+        holder: Some(tokio::spawn(hold_indexer_lock(
+            connection,
+            indexer_id.to_string(),
+        ))),
+    })
+}
+
+/// Internal function to keep the connection with the advisory lock alive.
+/// Stops the process if the connection is broken, because the lock could be already taken
+/// by the standby instance.
+async fn hold_indexer_lock(mut connection: sqlx::PgConnection, indexer_id: String) {
+    loop {
+        tokio::time::sleep(LOCK_HEALTH_CHECK_INTERVAL).await;
+        if let Err(err) = sqlx::query("SELECT 1").execute(&mut connection).await {
+            tracing::error!(
+                target: LOGGING_PREFIX,
+                "Lost the lock for INDEXER ID {}, stopping the instance\n{:#?}",
+                indexer_id,
+                err,
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Internal function to fetch the latest from a given `rpc-url`.
 /// Returns final block in the chain or throws an error.
 async fn fetch_latest_block_height_from_rpc(rpc_url: &str) -> anyhow::Result<u64> {
@@ -241,6 +695,57 @@ async fn fetch_latest_block_height_from_rpc(rpc_url: &str) -> anyhow::Result<u64
     Ok(latest_block.header.height)
 }
 
+/// Internal function to fetch the `start_block_height` stored in `__meta` table
+/// for the given `indexer-id`.
+/// Returns a block height [u64] or an error
+async fn fetch_start_block_height_from_db(
+    indexer_id: &str,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<u64> {
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(indexer_id);
+    let height: BigDecimal = sqlx::query_with(
+        r#"
+SELECT start_block_height FROM __meta WHERE indexer_id = $1
+        "#,
+        args,
+    )
+    .fetch_one(pool)
+    .await?
+    .get(0);
+    height
+        .to_u64()
+        .ok_or_else(|| anyhow::anyhow!("Failed to convert `start_block_height` to u64"))
+}
+
+/// Internal function to fetch the `last_processed_block_height` stored in `__meta` table
+/// for the given `indexer-id`.
+/// Returns `None` if the indexer is not registered yet
+async fn try_fetch_last_processed_block_height_from_db(
+    indexer_id: &str,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> anyhow::Result<Option<u64>> {
+    let mut args = sqlx::postgres::PgArguments::default();
+    args.add(indexer_id);
+    let row = sqlx::query_with(
+        r#"
+SELECT last_processed_block_height FROM __meta WHERE indexer_id = $1
+        "#,
+        args,
+    )
+    .fetch_optional(pool)
+    .await?;
+    match row {
+        Some(row) => {
+            let height: BigDecimal = row.get(0);
+            Ok(Some(height.to_u64().ok_or_else(|| {
+                anyhow::anyhow!("Failed to convert `last_processed_block_height` to u64")
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
 /// Internal function to fetch the `last_processed_block_height` stores in `__meta` table
 /// for the given `indexer-id`.
 /// Returns a block height [u64] or an error